@@ -0,0 +1,98 @@
+//! Compares the one-shot `FFT` against a reused `FftPlan` at the sizes the analyzer runs.
+//!
+//! run with `cargo bench --bench fft`, adding `--features simd` for the vectorized passes
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use num_complex::Complex;
+use tuner::{
+    audio_analysis::{AudioAnalyzer, WindowType},
+    dft::TransformType,
+    fft::{FftPlan, FFT},
+};
+
+// runs `f` until a second has passed and reports the mean time per run
+fn bench(name: &str, mut f: impl FnMut()) -> Duration {
+    f();
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+    let mean = start.elapsed() / runs;
+    println!("{:<40} {:>10.3} ms", name, mean.as_secs_f64() * 1000.0);
+    mean
+}
+
+fn signal(len: usize) -> Box<[f32]> {
+    (0..len)
+        .map(|i| (i as f32 * 0.0577).sin() + 0.25 * (i as f32 * 0.31).cos())
+        .collect()
+}
+
+fn main() {
+    for bits in [16, 17, 18] {
+        let size = 1 << bits;
+        let real = signal(size);
+        let complex = real
+            .iter()
+            .map(|value| Complex::new(*value, 0.0))
+            .collect::<Box<[_]>>();
+        let plan = FftPlan::new(size).unwrap();
+        println!("{} points", size);
+
+        let one_shot = bench("complex FFT::transform", || {
+            let mut fft = FFT::new(black_box(&real), TransformType::Forward);
+            black_box(fft.transform(false));
+        });
+        let mut buffer = complex.clone();
+        let planned = bench("complex FftPlan::process", || {
+            buffer.copy_from_slice(&complex);
+            plan.process(black_box(&mut buffer), TransformType::Forward, false);
+        });
+        println!(
+            "{:<40} {:>10.2}x",
+            "speedup",
+            one_shot.as_secs_f64() / planned.as_secs_f64()
+        );
+
+        let one_shot = bench("real FFT::real_fft", || {
+            black_box(FFT::real_fft(black_box(&real)));
+        });
+        let mut spectrum = vec![Complex::new(0.0, 0.0); size / 2 + 1];
+        let planned = bench("real FftPlan::real_forward", || {
+            plan.real_forward(black_box(&real), &mut spectrum);
+        });
+        println!(
+            "{:<40} {:>10.2}x",
+            "speedup",
+            one_shot.as_secs_f64() / planned.as_secs_f64()
+        );
+        println!();
+    }
+
+    // mixed radix at the analyzer's size, and a prime size for Bluestein
+    for size in [1024 * 50 * 4, 200_003] {
+        let real = signal(size);
+        let plan = FftPlan::new(size).unwrap();
+        let mut spectrum = vec![Complex::new(0.0, 0.0); size / 2 + 1];
+        let mut scratch = vec![Complex::new(0.0, 0.0); plan.scratch_len()];
+        bench(
+            &format!("real FftPlan::real_forward, {} points", size),
+            || {
+                plan.real_forward_with_scratch(black_box(&real), &mut spectrum, &mut scratch);
+            },
+        );
+    }
+
+    // the default configuration zero pads 50K samples into a 200K point transform
+    let mut analyzer = AudioAnalyzer::new(48000, 1024 * 50, 3, 3, 440, WindowType::Hann);
+    analyzer.add_samples(&signal(1024 * 50));
+    bench("AudioAnalyzer::strongest_freq", || {
+        black_box(analyzer.strongest_freq());
+    });
+}
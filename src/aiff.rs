@@ -0,0 +1,532 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+
+use crate::{
+    chunk::{bytes_remaining, read_chunk_bounds},
+    wav::{deinterleave, ChannelSelection},
+};
+
+mod error;
+
+pub use error::AiffError;
+
+const FORM_HEADER: [u8; 4] = [0x46, 0x4F, 0x52, 0x4D]; //FORM
+const AIFF_HEADER: [u8; 4] = [0x41, 0x49, 0x46, 0x46]; //AIFF
+const AIFC_HEADER: [u8; 4] = [0x41, 0x49, 0x46, 0x43]; //AIFC
+const COMM_HEADER: [u8; 4] = [0x43, 0x4F, 0x4D, 0x4D]; //COMM
+const SSND_HEADER: [u8; 4] = [0x53, 0x53, 0x4E, 0x44]; //SSND
+
+/// How the sample points in the SSND chunk are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// big endian two's complement PCM, the only encoding plain AIFF has
+    None,
+    /// little endian PCM (`sowt`), as written by many macOS tools
+    Sowt,
+    /// big endian 32 bit IEEE float (`fl32`)
+    Float32,
+    /// big endian 64 bit IEEE float (`fl64`)
+    Float64,
+}
+
+impl Compression {
+    fn from_tag(tag: [u8; 4]) -> Result<Self, AiffError> {
+        match &tag {
+            b"NONE" | b"twos" => Ok(Self::None),
+            b"sowt" => Ok(Self::Sowt),
+            b"fl32" | b"FL32" => Ok(Self::Float32),
+            b"fl64" | b"FL64" => Ok(Self::Float64),
+            _ => Err(AiffError::UnsupportedCompression(tag)),
+        }
+    }
+    fn tag(self) -> [u8; 4] {
+        match self {
+            Self::None => *b"NONE",
+            Self::Sowt => *b"sowt",
+            Self::Float32 => *b"fl32",
+            Self::Float64 => *b"fl64",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CommChunk {
+    channels: u16,
+    frame_count: u32,
+    bits_per_sample: u16,
+    sample_rate: f64,
+    compression: Compression,
+}
+
+impl CommChunk {
+    fn read<T: Read>(data: &mut T, header: &ChunkHeader, aifc: bool) -> Result<Self, AiffError> {
+        let invalid = |reason| AiffError::InvalidChunk {
+            chunk: header.id,
+            offset: header.offset,
+            reason,
+        };
+        let channels = data.read_i16::<BigEndian>()?;
+        let frame_count = data.read_u32::<BigEndian>()?;
+        let bits_per_sample = data.read_i16::<BigEndian>()?;
+        let mut sample_rate = [0u8; 10];
+        data.read_exact(&mut sample_rate)?;
+        let compression = if aifc {
+            let mut tag = [0u8; 4];
+            data.read_exact(&mut tag)?;
+            Compression::from_tag(tag)?
+        } else {
+            Compression::None
+        };
+
+        if channels <= 0 {
+            return Err(invalid("channel count must be positive"));
+        }
+        let sample_rate = extended_to_f64(sample_rate);
+        if !sample_rate.is_finite() || sample_rate < 1.0 || sample_rate > u32::MAX as f64 {
+            return Err(invalid("sample rate out of range"));
+        }
+        let container_bits = match compression {
+            Compression::None | Compression::Sowt => 1..=32,
+            Compression::Float32 => 32..=32,
+            Compression::Float64 => 64..=64,
+        };
+        if !container_bits.contains(&bits_per_sample) {
+            return Err(AiffError::UnsupportedSampleSize {
+                compression: compression.tag(),
+                bits_per_sample: bits_per_sample.max(0) as u16,
+            });
+        }
+        Ok(Self {
+            channels: channels as u16,
+            frame_count,
+            bits_per_sample: bits_per_sample as u16,
+            sample_rate,
+            compression,
+        })
+    }
+
+    // bytes one sample point is stored in
+    fn container_bytes(&self) -> usize {
+        (self.bits_per_sample as usize).div_ceil(8)
+    }
+}
+
+/// Decodes an 80 bit IEEE 754 extended precision number, as used for the COMM sample rate.
+fn extended_to_f64(bytes: [u8; 10]) -> f64 {
+    let sign = if bytes[0] & 0x80 != 0 { -1.0 } else { 1.0 };
+    let exponent = BigEndian::read_u16(&bytes[0..2]) & 0x7FFF;
+    let mantissa = BigEndian::read_u64(&bytes[2..10]);
+    if exponent == 0x7FFF {
+        return f64::NAN;
+    }
+    // the integer bit is explicit, so no implied leading one
+    sign * mantissa as f64 * 2f64.powi(exponent as i32 - 16383 - 63)
+}
+
+struct ChunkHeader {
+    id: [u8; 4],
+    size: u64,
+    // stream position of the chunk id
+    offset: u64,
+}
+
+impl ChunkHeader {
+    // reads the whole body, so parsing it can't run into the next chunk
+    fn read_body<T: Read>(&self, data: &mut T) -> Result<Vec<u8>, AiffError> {
+        let mut body = vec![0; self.size as usize];
+        data.read_exact(&mut body)?;
+        if self.size % 2 == 1 {
+            // the pad byte may be missing at the very end of the file
+            let _ = data.read_u8();
+        }
+        Ok(body)
+    }
+
+    fn parse<C>(
+        &self,
+        body: &[u8],
+        parse: impl FnOnce(&mut &[u8]) -> Result<C, AiffError>,
+    ) -> Result<C, AiffError> {
+        parse(&mut &body[..]).map_err(|error| error.truncated_at(self.id, self.offset))
+    }
+}
+
+fn read_chunk_header<T: Read + Seek>(data: &mut T) -> Result<ChunkHeader, AiffError> {
+    let bounds = read_chunk_bounds::<BigEndian, _, AiffError>(data, |_, _, size| Ok(size as u64))?;
+    Ok(ChunkHeader {
+        id: bounds.id,
+        size: bounds.size,
+        offset: bounds.offset,
+    })
+}
+
+/// An AIFF or AIFF-C file, decoded to the same interleaved f32 samples as `WavFile`.
+#[derive(Debug)]
+pub struct AiffFile {
+    form_type: [u8; 4],
+    comm_chunk: CommChunk,
+    data: Box<[u8]>,
+}
+
+impl AiffFile {
+    pub fn from_bytes<T: Read + Seek>(data: &mut T) -> Result<Self, AiffError> {
+        let offset = data.stream_position()?;
+        let available = bytes_remaining::<AiffError, _>(data)?;
+        let mut form_header = [0u8; 4];
+        data.read_exact(&mut form_header[..available.min(4) as usize])?;
+        if form_header != FORM_HEADER {
+            return Err(AiffError::BadFormHeader(form_header));
+        }
+        if available < 12 {
+            return Err(AiffError::Truncated {
+                chunk: FORM_HEADER,
+                offset,
+            });
+        }
+        let form_size = data.read_u32::<BigEndian>()? as u64;
+        let mut form_type = [0u8; 4];
+        data.read_exact(&mut form_type)?;
+        if form_type != AIFF_HEADER && form_type != AIFC_HEADER {
+            return Err(AiffError::BadFormType(form_type));
+        }
+        if form_size != available - 8 {
+            return Err(AiffError::FileSizeMismatch {
+                expected: form_size,
+                actual: available - 8,
+            });
+        }
+
+        let mut comm_chunk = None;
+        let mut sound_data = None;
+        while bytes_remaining::<AiffError, _>(data)? > 0 {
+            let header = read_chunk_header(data)?;
+            match header.id {
+                COMM_HEADER => {
+                    let body = header.read_body(data)?;
+                    let aifc = form_type == AIFC_HEADER;
+                    comm_chunk =
+                        Some(header.parse(&body, |body| CommChunk::read(body, &header, aifc))?);
+                }
+                SSND_HEADER => {
+                    let body = header.read_body(data)?;
+                    let start = header.parse(&body, |body| {
+                        let offset = body.read_u32::<BigEndian>()?;
+                        let _block_size = body.read_u32::<BigEndian>()?;
+                        Ok(8 + offset as u64)
+                    })?;
+                    if start > body.len() as u64 {
+                        return Err(AiffError::InvalidChunk {
+                            chunk: header.id,
+                            offset: header.offset,
+                            reason: "sample data offset past the end of the chunk",
+                        });
+                    }
+                    sound_data = Some(body[start as usize..].into());
+                }
+                // FVER, MARK, INST, NAME, ... are not needed for decoding
+                _ => {
+                    let padded_size = header.size + header.size % 2;
+                    let skip = padded_size.min(bytes_remaining::<AiffError, _>(data)?);
+                    data.seek(SeekFrom::Current(skip as i64))?;
+                }
+            }
+        }
+
+        let comm_chunk = comm_chunk.ok_or(AiffError::MissingChunk(COMM_HEADER))?;
+        // a file without sound data is valid as long as it claims no frames
+        let data = match sound_data {
+            Some(data) => data,
+            None if comm_chunk.frame_count == 0 => Box::default(),
+            None => return Err(AiffError::MissingChunk(SSND_HEADER)),
+        };
+        Ok(Self {
+            form_type,
+            comm_chunk,
+            data,
+        })
+    }
+
+    /// True for AIFF-C files, which may carry compressed or little endian samples.
+    pub fn is_aifc(&self) -> bool {
+        self.form_type == AIFC_HEADER
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.comm_chunk.compression
+    }
+
+    /// Sample rate rounded to the nearest Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.comm_chunk.sample_rate.round() as u32
+    }
+
+    /// Sample rate exactly as stored, which need not be a whole number.
+    pub fn exact_sample_rate(&self) -> f64 {
+        self.comm_chunk.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.comm_chunk.channels
+    }
+
+    pub fn bits_per_sample(&self) -> u16 {
+        self.comm_chunk.bits_per_sample
+    }
+
+    /// Number of sample frames, limited to the ones actually present in SSND.
+    pub fn frame_count(&self) -> u64 {
+        let block_align = self.comm_chunk.container_bytes() * self.comm_chunk.channels as usize;
+        (self.comm_chunk.frame_count as u64).min((self.data.len() / block_align) as u64)
+    }
+
+    /// Decodes the sound data into interleaved samples normalized to `[-1.0, 1.0)`.
+    pub fn get_samples(&self) -> Result<Box<[f32]>, AiffError> {
+        let container_bytes = self.comm_chunk.container_bytes();
+        let len = self.frame_count() as usize * self.comm_chunk.channels as usize;
+        let samples = self.data.chunks_exact(container_bytes).take(len);
+        let decoded = match (self.comm_chunk.compression, container_bytes) {
+            // AIFF 8 bit samples are signed, unlike WAVE
+            (Compression::None | Compression::Sowt, 1) => {
+                samples.map(|s| s[0] as i8 as f32 / 128.0).collect()
+            }
+            (Compression::None, 2) => samples
+                .map(|s| BigEndian::read_i16(s) as f32 / 32768.0)
+                .collect(),
+            (Compression::None, 3) => samples
+                .map(|s| BigEndian::read_i24(s) as f32 / 8388608.0)
+                .collect(),
+            (Compression::None, _) => samples
+                .map(|s| (BigEndian::read_i32(s) as f64 / 2147483648.0) as f32)
+                .collect(),
+            (Compression::Sowt, 2) => samples
+                .map(|s| LittleEndian::read_i16(s) as f32 / 32768.0)
+                .collect(),
+            (Compression::Sowt, 3) => samples
+                .map(|s| LittleEndian::read_i24(s) as f32 / 8388608.0)
+                .collect(),
+            (Compression::Sowt, _) => samples
+                .map(|s| (LittleEndian::read_i32(s) as f64 / 2147483648.0) as f32)
+                .collect(),
+            (Compression::Float32, _) => samples.map(BigEndian::read_f32).collect(),
+            (Compression::Float64, _) => samples.map(|s| BigEndian::read_f64(s) as f32).collect(),
+        };
+        Ok(decoded)
+    }
+
+    /// Decodes the sound data into one buffer per channel.
+    pub fn get_channels(&self) -> Result<Box<[Box<[f32]>]>, AiffError> {
+        let channels = self.comm_chunk.channels;
+        deinterleave(&self.get_samples()?, channels).map_err(|_| AiffError::InvalidChannel {
+            channel: 0,
+            channels,
+        })
+    }
+
+    /// Decodes the sound data down to the single signal picked by `selection`.
+    pub fn get_mono_samples(&self, selection: ChannelSelection) -> Result<Box<[f32]>, AiffError> {
+        let channels = self.comm_chunk.channels;
+        match selection {
+            ChannelSelection::Channel(channel) if channel >= channels => {
+                Err(AiffError::InvalidChannel { channel, channels })
+            }
+            _ => selection
+                .apply(&self.get_samples()?, channels)
+                .map_err(|_| AiffError::InvalidChannel {
+                    channel: 0,
+                    channels,
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+fn f64_to_extended(value: f64) -> [u8; 10] {
+    let bits = value.to_bits();
+    let exponent = (((bits >> 52) & 0x7FF) as i32 - 1023 + 16383) as u16;
+    let mantissa = ((bits & ((1 << 52) - 1)) | 1 << 52) << 11;
+    let mut bytes = [0u8; 10];
+    BigEndian::write_u16(&mut bytes[0..2], exponent);
+    BigEndian::write_u64(&mut bytes[2..10], mantissa);
+    bytes
+}
+
+#[cfg(test)]
+fn build_file(
+    form_type: &[u8; 4],
+    compression: &[u8; 4],
+    bits: i16,
+    channels: i16,
+    data: &[u8],
+) -> Vec<u8> {
+    use byteorder::WriteBytesExt;
+
+    let mut comm = vec![];
+    comm.write_i16::<BigEndian>(channels).unwrap();
+    comm.write_u32::<BigEndian>(
+        (data.len() / channels as usize / (bits as usize).div_ceil(8)) as u32,
+    )
+    .unwrap();
+    comm.write_i16::<BigEndian>(bits).unwrap();
+    comm.extend_from_slice(&f64_to_extended(48000.0));
+    if form_type == &AIFC_HEADER {
+        comm.extend_from_slice(compression);
+        // empty pascal string, padded
+        comm.extend_from_slice(&[0, 0]);
+    }
+    let mut bytes = FORM_HEADER.to_vec();
+    bytes.write_u32::<BigEndian>(0).unwrap();
+    bytes.extend_from_slice(form_type);
+    bytes.extend_from_slice(&COMM_HEADER);
+    bytes.write_u32::<BigEndian>(comm.len() as u32).unwrap();
+    bytes.extend_from_slice(&comm);
+    bytes.extend_from_slice(&SSND_HEADER);
+    bytes.write_u32::<BigEndian>(8 + data.len() as u32).unwrap();
+    bytes.extend_from_slice(&[0; 8]);
+    bytes.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        bytes.push(0);
+    }
+    let form_size = bytes.len() as u32 - 8;
+    BigEndian::write_u32(&mut bytes[4..8], form_size);
+    bytes
+}
+
+#[test]
+fn extended_sample_rates() {
+    // 44100 Hz as written by every AIFF encoder
+    let bytes = [0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0];
+    assert_eq!(extended_to_f64(bytes), 44100.0);
+    for rate in [8000.0, 22050.0, 48000.0, 96000.0, 11025.5] {
+        assert_eq!(extended_to_f64(f64_to_extended(rate)), rate);
+    }
+    assert!(extended_to_f64([0x7F, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0]).is_nan());
+}
+
+#[test]
+fn encodings() {
+    use std::io::Cursor;
+
+    let read = |bytes: Vec<u8>| AiffFile::from_bytes(&mut Cursor::new(bytes)).unwrap();
+
+    let aiff = read(build_file(
+        b"AIFF",
+        b"NONE",
+        16,
+        1,
+        &[0x40, 0x00, 0xC0, 0x00, 0x20, 0x00],
+    ));
+    assert!(!aiff.is_aifc());
+    assert_eq!(aiff.sample_rate(), 48000);
+    assert_eq!(&aiff.get_samples().unwrap()[..], &[0.5, -0.5, 0.25]);
+
+    let aiff = read(build_file(b"AIFF", b"NONE", 8, 1, &[0x40, 0xC0, 0x00]));
+    assert_eq!(&aiff.get_samples().unwrap()[..], &[0.5, -0.5, 0.0]);
+
+    let aiff = read(build_file(
+        b"AIFF",
+        b"NONE",
+        24,
+        2,
+        &[0x40, 0, 0, 0xE0, 0, 0],
+    ));
+    assert_eq!(&aiff.get_channels().unwrap()[1][..], &[-0.25]);
+
+    let aiff = read(build_file(
+        b"AIFC",
+        b"sowt",
+        16,
+        1,
+        &[0x00, 0x40, 0x00, 0xC0],
+    ));
+    assert_eq!(aiff.compression(), Compression::Sowt);
+    assert_eq!(&aiff.get_samples().unwrap()[..], &[0.5, -0.5]);
+
+    let mut data = vec![];
+    for sample in [0.5f32, -0.125] {
+        data.extend_from_slice(&sample.to_be_bytes());
+    }
+    let aiff = read(build_file(b"AIFC", b"fl32", 32, 1, &data));
+    assert_eq!(aiff.compression(), Compression::Float32);
+    assert_eq!(&aiff.get_samples().unwrap()[..], &[0.5, -0.125]);
+
+    let error = AiffFile::from_bytes(&mut Cursor::new(build_file(b"AIFC", b"ima4", 16, 1, &[])));
+    assert!(matches!(error, Err(AiffError::UnsupportedCompression(_))));
+    let error = AiffFile::from_bytes(&mut Cursor::new(build_file(b"AIFC", b"fl32", 16, 1, &[])));
+    assert!(matches!(
+        error,
+        Err(AiffError::UnsupportedSampleSize { .. })
+    ));
+}
+
+#[test]
+fn corrupt_files() {
+    use std::io::Cursor;
+
+    let base = build_file(b"AIFC", b"sowt", 16, 2, &[0x11; 64]);
+    let mut corpus = vec![];
+    for len in 0..base.len() {
+        let mut truncated = base[..len].to_vec();
+        corpus.push(truncated.clone());
+        if len >= 8 {
+            let form_size = len as u32 - 8;
+            BigEndian::write_u32(&mut truncated[4..8], form_size);
+            corpus.push(truncated);
+        }
+    }
+    for position in 0..base.len() - 64 {
+        for value in [0x00, 0x01, 0x7F, 0x80, 0xFF] {
+            let mut mutated = base.clone();
+            mutated[position] = value;
+            corpus.push(mutated);
+        }
+    }
+    for bytes in corpus {
+        if let Ok(aiff) = AiffFile::from_bytes(&mut Cursor::new(bytes)) {
+            let _ = aiff.get_channels();
+            let _ = aiff.get_mono_samples(ChannelSelection::Downmix);
+        }
+    }
+
+    // COMM cut off before the sample rate
+    let mut bytes = base[..20].to_vec();
+    bytes.extend_from_slice(&[0; 6]);
+    BigEndian::write_u32(&mut bytes[16..20], 6);
+    BigEndian::write_u32(&mut bytes[4..8], 26 - 8);
+    assert!(matches!(
+        AiffFile::from_bytes(&mut Cursor::new(bytes)),
+        Err(AiffError::Truncated {
+            chunk: COMM_HEADER,
+            offset: 12
+        })
+    ));
+}
+
+#[test]
+fn analyzes_like_wav() {
+    use crate::{
+        audio_analysis::{AudioAnalyzer, Note, WindowType},
+        wav::WavFile,
+    };
+    use std::io::Cursor;
+
+    let wav = WavFile::from_bytes(&mut Cursor::new(include_bytes!(".././A.wav"))).unwrap();
+    let samples = wav.get_samples().unwrap();
+    let mut float_data = vec![];
+    let mut pcm_data = vec![];
+    for sample in samples.iter() {
+        float_data.extend_from_slice(&sample.to_be_bytes());
+        pcm_data.extend_from_slice(&((sample * 32767.0) as i16).to_be_bytes());
+    }
+
+    let aifc = build_file(b"AIFC", b"fl32", 32, 1, &float_data);
+    let aifc = AiffFile::from_bytes(&mut Cursor::new(aifc)).unwrap();
+    assert_eq!(aifc.get_samples().unwrap(), samples);
+
+    let aiff = build_file(b"AIFF", b"NONE", 16, 1, &pcm_data);
+    let aiff = AiffFile::from_bytes(&mut Cursor::new(aiff)).unwrap();
+    let mut analyzer =
+        AudioAnalyzer::new(aiff.sample_rate(), 1024 * 50, 0, 3, 440, WindowType::Hann);
+    analyzer.add_samples(&aiff.get_samples().unwrap());
+    assert_eq!(Note::from_frequency(analyzer.strongest_freq()), Note::A);
+}
@@ -0,0 +1,164 @@
+use std::{error::Error, fmt, io};
+
+use crate::chunk::ChunkError;
+
+/// Everything that can go wrong reading an AIFF or AIFF-C file.
+///
+/// `offset` is where the id of the offending chunk starts, counting the FORM id as byte 0.
+#[derive(Debug)]
+pub enum AiffError {
+    Io(io::Error),
+    /// the stream does not start with FORM
+    BadFormHeader([u8; 4]),
+    /// the form type is neither AIFF nor AIFC
+    BadFormType([u8; 4]),
+    /// the FORM size disagrees with the length of the stream
+    FileSizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    /// a chunk's size runs past the end of the FORM
+    ChunkOverrun {
+        chunk: [u8; 4],
+        offset: u64,
+        size: u64,
+        available: u64,
+    },
+    /// the stream ended inside a chunk header, or a COMM or SSND chunk is too short for
+    /// its fixed fields
+    Truncated {
+        chunk: [u8; 4],
+        offset: u64,
+    },
+    /// a COMM chunk with no channels or an impossible sample rate, or an SSND chunk whose
+    /// data offset is past its end
+    InvalidChunk {
+        chunk: [u8; 4],
+        offset: u64,
+        reason: &'static str,
+    },
+    /// no COMM chunk, or no SSND chunk though COMM counts frames
+    MissingChunk([u8; 4]),
+    /// an AIFF-C compression type other than the uncompressed ones
+    UnsupportedCompression([u8; 4]),
+    /// a sample size the compression type can't hold
+    UnsupportedSampleSize {
+        compression: [u8; 4],
+        bits_per_sample: u16,
+    },
+    /// a channel index at or above the channel count
+    InvalidChannel {
+        channel: u16,
+        channels: u16,
+    },
+}
+
+fn chunk_name(chunk: &[u8; 4]) -> String {
+    String::from_utf8_lossy(chunk).into_owned()
+}
+
+impl fmt::Display for AiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AiffError::Io(error) => write!(f, "io error: {}", error),
+            AiffError::BadFormHeader(found) => {
+                write!(f, "bad FORM header {:?}", chunk_name(found))
+            }
+            AiffError::BadFormType(found) => write!(f, "bad form type {:?}", chunk_name(found)),
+            AiffError::FileSizeMismatch { expected, actual } => write!(
+                f,
+                "header says {} bytes follow the FORM size but the stream has {}",
+                expected, actual
+            ),
+            AiffError::ChunkOverrun {
+                chunk,
+                offset,
+                size,
+                available,
+            } => write!(
+                f,
+                "{:?} chunk at byte {} claims {} bytes but only {} are left",
+                chunk_name(chunk),
+                offset,
+                size,
+                available
+            ),
+            AiffError::Truncated { chunk, offset } => write!(
+                f,
+                "{:?} chunk at byte {} ends before all of its fields",
+                chunk_name(chunk),
+                offset
+            ),
+            AiffError::InvalidChunk {
+                chunk,
+                offset,
+                reason,
+            } => write!(
+                f,
+                "{:?} chunk at byte {} is invalid: {}",
+                chunk_name(chunk),
+                offset,
+                reason
+            ),
+            AiffError::MissingChunk(chunk) => write!(f, "No {:?} chunk", chunk_name(chunk)),
+            AiffError::UnsupportedCompression(compression) => {
+                write!(f, "unsupported compression {:?}", chunk_name(compression))
+            }
+            AiffError::UnsupportedSampleSize {
+                compression,
+                bits_per_sample,
+            } => write!(
+                f,
+                "{} bits per sample are not supported with {:?} samples",
+                bits_per_sample,
+                chunk_name(compression)
+            ),
+            AiffError::InvalidChannel { channel, channels } => write!(
+                f,
+                "channel {} out of range for {} channels",
+                channel, channels
+            ),
+        }
+    }
+}
+
+impl Error for AiffError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AiffError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AiffError {
+    fn from(error: io::Error) -> Self {
+        AiffError::Io(error)
+    }
+}
+
+impl ChunkError for AiffError {
+    fn truncated(chunk: [u8; 4], offset: u64) -> Self {
+        AiffError::Truncated { chunk, offset }
+    }
+    fn overrun(chunk: [u8; 4], offset: u64, size: u64, available: u64) -> Self {
+        AiffError::ChunkOverrun {
+            chunk,
+            offset,
+            size,
+            available,
+        }
+    }
+}
+
+impl AiffError {
+    // reads of bounded chunk bodies can only fail by running out of bytes
+    pub(super) fn truncated_at(self, chunk: [u8; 4], offset: u64) -> Self {
+        match self {
+            AiffError::Io(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                AiffError::Truncated { chunk, offset }
+            }
+            error => error,
+        }
+    }
+}
@@ -37,13 +37,16 @@ use crate::{
     audio_analysis::{find_max_float, AudioAnalyzer, Note},
     circular_buffer::CircularBuffer,
     fft::FFT,
-    wav::WavFile,
+    resample::{Quality, Resampler},
+    wav::{ChannelSelection, WavFile},
 };
 
 pub const DEFAULT_WINDOW_TITLE: &str = "dodge left dodge right";
 pub const DEFAULT_WIDTH: usize = 800;
 pub const DEFAULT_HEIGHT: usize = 600;
 pub const BUFFER_SIZE: usize = 8192;
+// device input is converted to this rate, so the analyzer's resolution doesn't depend on the device
+pub const ANALYSIS_SAMPLE_RATE: u32 = 48000;
 unsafe fn get_glow_context(window: &Window) -> glow::Context {
     unsafe {
         glow::Context::from_loader_function(|s| window.subsystem().gl_get_proc_address(s) as _)
@@ -177,7 +180,7 @@ impl AppBuilder {
             audio_host,
             sample_buffer: Arc::new(Mutex::new(CircularBuffer::new(2048))),
             audio_analyzer: Arc::new(Mutex::new(AudioAnalyzer::new(
-                48000,
+                ANALYSIS_SAMPLE_RATE,
                 1024 * 50,
                 3,
                 3,
@@ -563,10 +566,30 @@ impl App {
                 let config = config.unwrap().with_max_sample_rate().config();
                 let cloned_arc = sample_buffer.clone();
                 let analyzer_arc = audio_analyzer.clone();
+                let channels = config.channels;
+                let mut resampler =
+                    Resampler::new(config.sample_rate.0, ANALYSIS_SAMPLE_RATE, Quality::Fast);
+                // reused by every callback, so the audio thread only allocates if a block
+                // outgrows them
+                let mut mono = Vec::with_capacity(BUFFER_SIZE);
+                let mut resampled = Vec::with_capacity(
+                    (BUFFER_SIZE as u64 * ANALYSIS_SAMPLE_RATE as u64)
+                        .div_ceil(config.sample_rate.0 as u64) as usize
+                        + 1,
+                );
                 let stream = device.build_input_stream(
                     &config,
-                    move |a, _| {
-                        Self::write_callback(a, &cloned_arc, &analyzer_arc);
+                    move |a: &[f32], _| {
+                        // the resampler filters a single channel, so interleaved frames are
+                        // mixed down first
+                        if ChannelSelection::Downmix
+                            .apply_into(a, channels, &mut mono)
+                            .is_err()
+                        {
+                            return;
+                        }
+                        resampler.process_into(&mono, &mut resampled);
+                        Self::write_callback(&resampled, &cloned_arc, &analyzer_arc);
                     },
                     move |_| {},
                     None,
@@ -574,7 +597,7 @@ impl App {
                 let mut audio_analyzer = audio_analyzer.lock().unwrap();
                 let mut sample_buffer = sample_buffer.lock().unwrap();
                 let new_analyzer = AudioAnalyzer::new(
-                    config.sample_rate.0,
+                    ANALYSIS_SAMPLE_RATE,
                     1024 * 50,
                     3,
                     3,
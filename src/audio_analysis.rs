@@ -2,22 +2,31 @@ use anyhow::anyhow;
 use num_complex::{Complex, ComplexFloat};
 use std::{
     array,
-    f32::consts::PI,
     io::{copy, Cursor},
     ops::Not,
     sync::{Arc, Mutex},
 };
 
+pub use crate::window::WindowType;
 use crate::{
     circular_buffer::CircularBuffer,
-    fft::{lower_power_of_two, FFT},
+    fft::{FftPlan, FFT},
     wav::WavFile,
+    window::{Symmetry, Window},
+    yin::{Pyin, Yin},
+    zoom::ZoomFft,
 };
 pub const NOTE_NAMES: [&'static str; 12] = [
     "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
 ];
 pub const EMPTY_STR: &'static str = "";
 pub const A4_FREQUENCY: u32 = 440;
+// how far either side of a target `strongest_freq` looks, and how finely, in cents
+const TARGET_RANGE: f32 = 60.0;
+const TARGET_STEP: f32 = 0.1;
+const TARGET_BINS: usize = (2.0 * TARGET_RANGE / TARGET_STEP) as usize + 1;
+// lowest and highest pitch the time domain methods look for, in Hz
+pub const PITCH_RANGE: (f32, f32) = (60.0, 2000.0);
 
 #[derive(Debug, Clone, Copy)]
 pub enum SampleRate {
@@ -105,20 +114,47 @@ impl Note {
 }
 
 pub struct AudioAnalyzer {
-    window: Box<[f32]>,
+    window: Window,
     buffer: CircularBuffer<f32>,
     padded_buffer: Box<[f32]>,
     hps_count: usize,
     a4_freq: u32,
     sample_rate: u32,
+    fft_plan: FftPlan,
+    spectrum: Box<[Complex<f32>]>,
+    fft_scratch: Box<[Complex<f32>]>,
+    // frequency of each bin from DC up to, but not including, Nyquist
+    freq_table: Box<[f32]>,
     result_buffer: Box<[f32]>,
+    // the zoomed bins around a target and their log magnitudes
+    zoom_buffer: Box<[Complex<f32>]>,
+    zoom_magnitudes: Box<[f32]>,
+    // the frequency set by `set_target` and the band around it
+    target: Option<(f32, ZoomFft)>,
+    pitch: PitchDetector,
 }
-pub enum WindowType {
-    Hamming,
-    Hann,
+
+/// How `strongest_freq` finds the pitch when there's no target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PitchMethod {
+    /// the loudest bin of the spectrum, after the harmonic product spectrum if `hps_count`
+    /// is above 1
+    Spectrum,
+    /// YIN on the unwindowed buffer, which finds the fundamental however weak it is
+    Yin,
+    /// pYIN, following the likeliest path from one call to the next
+    Pyin,
+}
+
+enum PitchDetector {
+    Spectrum,
+    Yin(Yin),
+    Pyin(Pyin),
 }
 
 impl AudioAnalyzer {
+    /// # Panics
+    /// If `buffer_size` is zero.
     pub fn new(
         sample_rate: u32,
         buffer_size: usize,
@@ -127,56 +163,108 @@ impl AudioAnalyzer {
         a4_freq: u32,
         window_type: WindowType,
     ) -> Self {
-        let window = match window_type {
-            WindowType::Hamming => Self::build_hamming_window(lower_power_of_two(buffer_size)),
-            WindowType::Hann => Self::build_hann_window(lower_power_of_two(buffer_size)),
-        };
+        assert!(buffer_size > 0, "the analysis buffer can't be empty");
+        let window = Window::new(window_type, buffer_size, Symmetry::Periodic);
 
+        let padded_len = buffer_size * (1 + zero_padding_factor);
+        let fft_plan = FftPlan::new(padded_len).unwrap();
         Self {
             window,
-            buffer: CircularBuffer::new(lower_power_of_two(buffer_size)),
-            padded_buffer: vec![0.0; lower_power_of_two(buffer_size * (1 + zero_padding_factor))]
-                .into_boxed_slice(),
+            buffer: CircularBuffer::new(buffer_size),
+            padded_buffer: vec![0.0; padded_len].into_boxed_slice(),
             a4_freq,
             hps_count,
             sample_rate,
-            result_buffer: vec![
-                0.0;
-                lower_power_of_two(buffer_size * (1 + zero_padding_factor)) / 2
-            ]
-            .into_boxed_slice(),
+            spectrum: vec![Complex::new(0.0, 0.0); padded_len / 2 + 1].into_boxed_slice(),
+            fft_scratch: vec![Complex::new(0.0, 0.0); fft_plan.scratch_len()].into_boxed_slice(),
+            fft_plan,
+            freq_table: FFT::freq_table(padded_len as u32, 1.0 / sample_rate as f32)
+                [..padded_len / 2]
+                .into(),
+            result_buffer: vec![0.0; padded_len / 2].into_boxed_slice(),
+            zoom_buffer: vec![Complex::new(0.0, 0.0); TARGET_BINS].into_boxed_slice(),
+            zoom_magnitudes: vec![0.0; TARGET_BINS].into_boxed_slice(),
+            target: None,
+            pitch: PitchDetector::Spectrum,
+        }
+    }
+
+    /// Switches between finding the pitch in the spectrum and in the time domain. The time
+    /// domain methods look between 60 Hz and 2 kHz, and `strongest_freq` gives 0 when they
+    /// find no clear pitch.
+    ///
+    /// # Panics
+    /// For `Yin` and `Pyin`, if the buffer is shorter than `Yin::min_frame_len` for 60 Hz.
+    pub fn set_pitch_method(&mut self, method: PitchMethod) {
+        let (low, high) = PITCH_RANGE;
+        let frame_len = self.window.size();
+        self.pitch = match method {
+            PitchMethod::Spectrum => PitchDetector::Spectrum,
+            PitchMethod::Yin => {
+                PitchDetector::Yin(Yin::new(self.sample_rate, frame_len, low, high))
+            }
+            PitchMethod::Pyin => {
+                PitchDetector::Pyin(Pyin::new(self.sample_rate, frame_len, low, high))
+            }
+        };
+    }
+
+    pub fn pitch_method(&self) -> PitchMethod {
+        match self.pitch {
+            PitchDetector::Spectrum => PitchMethod::Spectrum,
+            PitchDetector::Yin(_) => PitchMethod::Yin,
+            PitchDetector::Pyin(_) => PitchMethod::Pyin,
         }
     }
 
+    /// Switches `strongest_freq` to looking only within a little over half a semitone of
+    /// `frequency`, which it then resolves to a small fraction of a cent. For fine tuning
+    /// once the note is known; `None` goes back to searching the whole spectrum.
+    pub fn set_target(&mut self, frequency: Option<f32>) {
+        self.target = frequency.map(|frequency| {
+            let zoom = ZoomFft::new(
+                self.sample_rate,
+                self.window.size(),
+                frequency * 2.0f32.powf(-TARGET_RANGE / 1200.0),
+                frequency * 2.0f32.powf(TARGET_RANGE / 1200.0),
+                TARGET_BINS,
+            );
+            (frequency, zoom)
+        });
+    }
+
+    pub fn target(&self) -> Option<f32> {
+        self.target.as_ref().map(|(frequency, _)| *frequency)
+    }
+
     pub fn add_samples(&mut self, samples: &[f32]) {
         samples.iter().for_each(|sample| {
             self.buffer.push_back(*sample);
         });
     }
 
+    /// The window each buffer is multiplied by, periodic like all analysis windows here.
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
     pub fn build_hamming_window(size: usize) -> Box<[f32]> {
-        (0..size)
-            .map(|i| {
-                let val = 0.54 - 0.46 * (2.0 * PI * i as f32 / size as f32).cos();
-                val
-            })
-            .collect::<Box<[f32]>>()
+        Window::new(WindowType::Hamming, size, Symmetry::Periodic)
+            .samples()
+            .into()
     }
 
     pub fn build_hann_window(size: usize) -> Box<[f32]> {
-        (0..size)
-            .map(|i| {
-                let val = 0.5 * (1.0 - (2.0 * PI * i as f32 / (size as f32 - 1.0)).cos());
-                val
-            })
-            .collect::<Box<[f32]>>()
+        Window::new(WindowType::Hann, size, Symmetry::Periodic)
+            .samples()
+            .into()
     }
 
     fn copy_to_zero_padded_buffer(&mut self) {
         let len = self.buffer.len();
         self.buffer
             .iter()
-            .zip(self.window.iter())
+            .zip(self.window.samples().iter())
             .zip(self.padded_buffer.iter_mut())
             .for_each(|((sample, window_value), dest)| *dest = sample * window_value);
         self.padded_buffer
@@ -201,41 +289,91 @@ impl AudioAnalyzer {
     }
 
     pub fn strongest_freq(&mut self) -> f32 {
-        self.copy_to_zero_padded_buffer();
-        let mut fft = FFT::new(&self.padded_buffer, crate::dft::TransformType::Forward);
-        let mut result = fft
-            .transform(false)
-            .iter_mut()
-            .map(|f| f.abs())
-            .collect::<Box<[f32]>>();
-
-        let freq_table = FFT::freq_table(
-            ((result.len()) as u32).try_into().unwrap(),
-            1.0 / self.sample_rate as f32,
+        // the time domain methods read the buffer unwindowed, unless there's a target
+        let time_domain = !matches!(self.pitch, PitchDetector::Spectrum);
+        if self.target.is_some() || !time_domain {
+            self.copy_to_zero_padded_buffer();
+        }
+        if let Some((_, zoom)) = &mut self.target {
+            let frequency = Self::zoomed_peak(
+                zoom,
+                &self.padded_buffer[..self.window.size()],
+                &mut self.zoom_buffer,
+                &mut self.zoom_magnitudes,
+            );
+            return (frequency * 100.0).round() / 100.0;
+        }
+        if time_domain {
+            let frame = &mut self.padded_buffer[..self.window.size()];
+            frame.fill(0.0);
+            self.buffer
+                .iter()
+                .zip(frame.iter_mut())
+                .for_each(|(sample, dest)| *dest = *sample);
+            let estimate = match &mut self.pitch {
+                PitchDetector::Spectrum => None,
+                PitchDetector::Yin(yin) => yin.detect(frame),
+                PitchDetector::Pyin(pyin) => pyin.process(frame),
+            };
+            return estimate.map_or(0.0, |estimate| (estimate.frequency * 100.0).round() / 100.0);
+        }
+        self.fft_plan.real_forward_with_scratch(
+            &self.padded_buffer,
+            &mut self.spectrum,
+            &mut self.fft_scratch,
         );
 
-        let half_len = result.len() / 2;
-        let half_data = &mut result[0..half_len];
-        Self::apply_harmonic_product_spectrum(self.hps_count, half_data);
+        // the Nyquist bin is left out, as it always has been
+        self.result_buffer
+            .iter_mut()
+            .zip(self.spectrum.iter())
+            .for_each(|(magnitude, bin)| *magnitude = bin.abs());
+        Self::apply_harmonic_product_spectrum(self.hps_count, &mut self.result_buffer);
 
-        for (i, freq) in freq_table.iter().enumerate() {
+        for (i, freq) in self.freq_table.iter().enumerate() {
             if *freq > 60.0 {
-                half_data[..i].iter_mut().for_each(|f| *f = 0.0);
+                self.result_buffer[..i].iter_mut().for_each(|f| *f = 0.0);
                 break;
             }
         }
 
-        let loudest_tone_index = half_data
+        let loudest_tone_index = self
+            .result_buffer
             .iter()
             .enumerate()
             .max_by(|(_, a), (_, b)| a.total_cmp(b))
             .map(|(index, _)| index)
             .unwrap();
 
-        let loudest_freq = (freq_table[loudest_tone_index] * 100.0).round() / 100.0;
-
-        self.result_buffer.copy_from_slice(&half_data);
-        loudest_freq
+        (self.freq_table[loudest_tone_index] * 100.0).round() / 100.0
+    }
+    // the loudest zoomed bin, placed between its neighbours by fitting a parabola to their
+    // log magnitudes, which is exact for a Gaussian peak and close for a windowed sine
+    fn zoomed_peak(
+        zoom: &mut ZoomFft,
+        samples: &[f32],
+        bins: &mut [Complex<f32>],
+        magnitudes: &mut [f32],
+    ) -> f32 {
+        zoom.process_into(samples, bins);
+        magnitudes
+            .iter_mut()
+            .zip(bins.iter())
+            .for_each(|(magnitude, bin)| *magnitude = bin.norm().max(f32::MIN_POSITIVE).ln());
+        let (peak, _) = find_max_float(magnitudes);
+        if peak == 0 || peak == magnitudes.len() - 1 {
+            return zoom.frequency(peak as f32);
+        }
+        let (left, center, right) = (magnitudes[peak - 1], magnitudes[peak], magnitudes[peak + 1]);
+        let curvature = left - 2.0 * center + right;
+        let offset = match curvature < 0.0 {
+            true => 0.5 * (left - right) / curvature,
+            false => 0.0,
+        };
+        zoom.frequency(peak as f32 + offset)
+    }
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
     }
     pub fn get_result_buffer(&self) -> &[f32] {
         &self.result_buffer
@@ -262,7 +400,7 @@ fn test_analysis() {
         WindowType::Hann,
     );
 
-    analyzer.add_samples(wav.get_samples());
+    analyzer.add_samples(&wav.get_samples().unwrap());
     let a = analyzer.strongest_freq();
     assert_eq!(Note::from_frequency(a), Note::A);
     let bytes = include_bytes!(".././A_RECORDING.wav");
@@ -278,7 +416,7 @@ fn test_analysis() {
         WindowType::Hann,
     );
 
-    analyzer.add_samples(wav.get_samples());
+    analyzer.add_samples(&wav.get_samples().unwrap());
     let a = analyzer.strongest_freq();
     assert_eq!(Note::from_frequency(a), Note::A);
     let bytes = include_bytes!(".././B.wav");
@@ -294,7 +432,7 @@ fn test_analysis() {
         WindowType::Hann,
     );
 
-    analyzer.add_samples(wav.get_samples());
+    analyzer.add_samples(&wav.get_samples().unwrap());
     let b = analyzer.strongest_freq();
     assert_eq!(Note::from_frequency(b), Note::B);
 }
@@ -308,3 +446,60 @@ fn test_note_to_str() {
     println!("freq: {}", freq);
     println!("note: {}", note);
 }
+
+#[test]
+fn target_mode() {
+    // 440 Hz raised by 3.3 cents, which falls between two bins of the full spectrum
+    let frequency = 440.0 * 2.0f64.powf(3.3 / 1200.0);
+    let samples = (0..1024 * 50)
+        .map(|i| (2.0 * std::f64::consts::PI * frequency * i as f64 / 48000.0).sin() as f32)
+        .collect::<Box<[f32]>>();
+    let cents = |found: f32| 1200.0 * (found as f64 / frequency).log2();
+
+    let mut analyzer = AudioAnalyzer::new(48000, 1024 * 50, 0, 3, 440, WindowType::Hann);
+    analyzer.add_samples(&samples);
+    let coarse = analyzer.strongest_freq();
+    assert_eq!(Note::from_frequency(coarse), Note::A);
+
+    analyzer.set_target(Some(Note::number_to_freq(69.0, 440)));
+    assert_eq!(analyzer.target(), Some(440.0));
+    let fine = analyzer.strongest_freq();
+    assert!(cents(fine).abs() < 0.1, "{} cents off", cents(fine));
+    assert!(cents(fine).abs() < cents(coarse).abs());
+
+    analyzer.set_target(None);
+    assert_eq!(analyzer.strongest_freq(), coarse);
+}
+
+#[test]
+fn pitch_methods() {
+    for method in [PitchMethod::Yin, PitchMethod::Pyin] {
+        for (file, note) in [
+            (&include_bytes!(".././A.wav")[..], Note::A),
+            (&include_bytes!(".././A_RECORDING.wav")[..], Note::A),
+            (&include_bytes!(".././B.wav")[..], Note::B),
+        ] {
+            let wav = WavFile::from_bytes(&mut Cursor::new(file)).unwrap();
+            let mut analyzer = AudioAnalyzer::new(48000, 1024 * 50, 0, 3, 440, WindowType::Hann);
+            analyzer.set_pitch_method(method);
+            assert_eq!(analyzer.pitch_method(), method);
+            analyzer.add_samples(&wav.get_samples().unwrap());
+            assert_eq!(Note::from_frequency(analyzer.strongest_freq()), note);
+        }
+    }
+
+    // low E with a weak fundamental under strong overtones, where the loudest bin is an
+    // octave up
+    let samples = crate::yin::harmonic_tone(82.41, 8192);
+    let mut analyzer = AudioAnalyzer::new(48000, 8192, 0, 3, 440, WindowType::Hann);
+    analyzer.add_samples(&samples);
+    let spectrum = Note::freq_to_number(analyzer.strongest_freq(), 440).round();
+    analyzer.set_pitch_method(PitchMethod::Yin);
+    let yin = Note::freq_to_number(analyzer.strongest_freq(), 440);
+    assert_eq!(spectrum, 52.0);
+    assert!((yin - 40.0).abs() < 0.02, "{}", yin);
+
+    // and silence has no pitch at all
+    analyzer.add_samples(&[0.0; 8192]);
+    assert_eq!(analyzer.strongest_freq(), 0.0);
+}
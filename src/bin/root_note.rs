@@ -0,0 +1,266 @@
+//! Detects the pitch of instrument samples and stores it as the `smpl` root note.
+//!
+//! usage: root_note [--a4 <hz>] [--hps <count>] [--rate <hz>] [--method <method>] <file>...
+//!
+//! Files can be WAVE or AIFF. AIFF files have no `smpl` chunk to hold the root note, so
+//! their pitch is only reported and the file is left unchanged.
+//!
+//! `--hps 0` turns off the harmonic product spectrum for samples without overtones.
+//! `--method yin` or `--method pyin` finds the note in the time domain instead of the
+//! spectrum, which copes better with a weak fundamental; the default is `spectrum`.
+//! `--rate` converts samples to that rate before analysis; lower rates resolve low
+//! notes more finely for the same buffer length.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{BufWriter, Cursor, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context};
+use tuner::{
+    aiff::AiffFile,
+    audio_analysis::{
+        AudioAnalyzer, Note, PitchMethod, WindowType, A4_FREQUENCY, NOTE_NAMES, PITCH_RANGE,
+    },
+    resample::{resample, Quality},
+    wav::{ChannelSelection, Sampler, WavFile},
+    yin::Yin,
+};
+
+// longest stretch of a sample the analyzer looks at, taken from the start so that
+// plucked and struck notes are analyzed before they decay
+const MAX_BUFFER_SIZE: usize = 1 << 16;
+
+struct Options {
+    a4_freq: u32,
+    hps_count: usize,
+    analysis_rate: Option<u32>,
+    method: PitchMethod,
+}
+
+fn detect_root_note(
+    mut samples: Box<[f32]>,
+    mut sample_rate: u32,
+    options: &Options,
+) -> Option<f32> {
+    if let Some(analysis_rate) = options.analysis_rate {
+        samples = resample(&samples, sample_rate, analysis_rate, Quality::Best);
+        sample_rate = analysis_rate;
+    }
+    let buffer_size = samples.len().min(MAX_BUFFER_SIZE);
+    if buffer_size == 0 {
+        return None;
+    }
+    let mut analyzer = AudioAnalyzer::new(
+        sample_rate,
+        buffer_size,
+        options.hps_count,
+        3,
+        options.a4_freq,
+        WindowType::Hann,
+    );
+    if options.method != PitchMethod::Spectrum {
+        // the time domain methods need two periods of their lowest pitch
+        let (low, _) = PITCH_RANGE;
+        if buffer_size < Yin::min_frame_len(sample_rate, low) {
+            return None;
+        }
+        analyzer.set_pitch_method(options.method);
+    }
+    analyzer.add_samples(&samples[..buffer_size]);
+    let freq = analyzer.strongest_freq();
+    if freq <= 0.0 {
+        return None;
+    }
+    // the full spectrum only finds the note, so zoom in on it for the cents
+    let nearest = Note::freq_to_number(freq, options.a4_freq).round();
+    analyzer.set_target(Some(Note::number_to_freq(nearest, options.a4_freq)));
+    Some(Note::freq_to_number(
+        analyzer.strongest_freq(),
+        options.a4_freq,
+    ))
+}
+
+// the note name and the cents above it, for a MIDI note number
+fn describe(unity_note: u32, note: f32) -> String {
+    format!(
+        "{}{} +{:.1} cents",
+        NOTE_NAMES[unity_note as usize % 12],
+        unity_note as i32 / 12 - 1,
+        (note - unity_note as f32) * 100.0
+    )
+}
+
+fn tag_file(path: &Path, options: &Options) -> Result<(), anyhow::Error> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(b"FORM") {
+        let aiff = AiffFile::from_bytes(&mut Cursor::new(bytes))?;
+        let samples = aiff.get_mono_samples(ChannelSelection::Downmix)?;
+        match detect_root_note(samples, aiff.sample_rate(), options) {
+            Some(note) => println!(
+                "{}: {}, not tagged as AIFF has no smpl chunk",
+                path.display(),
+                describe(note.clamp(0.0, 127.0).floor() as u32, note)
+            ),
+            None => println!("{}: no pitch found", path.display()),
+        }
+        return Ok(());
+    }
+
+    let mut wav = WavFile::from_bytes(&mut Cursor::new(bytes))?;
+    let samples = wav.get_mono_samples(ChannelSelection::Downmix)?;
+    let Some(note) = detect_root_note(samples, wav.sample_rate(), options) else {
+        println!("{}: no pitch found, left unchanged", path.display());
+        return Ok(());
+    };
+
+    let sample_rate = wav.sample_rate();
+    let sampler = wav
+        .metadata_mut()
+        .sampler
+        .get_or_insert_with(|| Sampler::new(sample_rate, note));
+    sampler.set_root_note(note);
+    println!(
+        "{}: {}",
+        path.display(),
+        describe(sampler.midi_unity_note, note)
+    );
+
+    // write next to the original so a failure never leaves a half written sample
+    let temp_path = path.with_extension("wav.tmp");
+    let mut out = BufWriter::new(File::create(&temp_path)?);
+    wav.write_to(&mut out)?;
+    out.flush()?;
+    drop(out);
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut options = Options {
+        a4_freq: A4_FREQUENCY,
+        hps_count: 3,
+        analysis_rate: None,
+        method: PitchMethod::Spectrum,
+    };
+    let mut paths = vec![];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--a4" => {
+                options.a4_freq = args
+                    .next()
+                    .ok_or(anyhow!("--a4 needs a frequency"))?
+                    .parse()
+                    .context("--a4 needs a whole number of Hz")?
+            }
+            "--hps" => {
+                options.hps_count = args
+                    .next()
+                    .ok_or(anyhow!("--hps needs a count"))?
+                    .parse()
+                    .context("--hps needs a whole number")?
+            }
+            "--rate" => {
+                let rate = args
+                    .next()
+                    .ok_or(anyhow!("--rate needs a sample rate"))?
+                    .parse()
+                    .context("--rate needs a whole number of Hz")?;
+                if rate == 0 {
+                    return Err(anyhow!("--rate needs a positive sample rate"));
+                }
+                options.analysis_rate = Some(rate);
+            }
+            "--method" => {
+                options.method = match args
+                    .next()
+                    .ok_or(anyhow!("--method needs a method"))?
+                    .as_str()
+                {
+                    "spectrum" => PitchMethod::Spectrum,
+                    "yin" => PitchMethod::Yin,
+                    "pyin" => PitchMethod::Pyin,
+                    _ => return Err(anyhow!("--method needs spectrum, yin or pyin")),
+                };
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err(anyhow!(
+            "usage: root_note [--a4 <hz>] [--hps <count>] [--rate <hz>] [--method <method>] <file>..."
+        ));
+    }
+
+    for path in paths {
+        tag_file(Path::new(&path), &options).with_context(|| format!("tagging {}", path))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn analyzes_the_start_of_long_samples() {
+    use tuner::wav::{WavFormat, WavSpec, WavWriter};
+
+    // a 220 Hz pluck that has decayed below 16 bit silence long before it ends
+    let sample_rate = 48000;
+    let samples: Vec<f32> = (0..sample_rate * 4)
+        .map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            let tone = (1..=3)
+                .map(|harmonic| {
+                    (std::f32::consts::TAU * 220.0 * harmonic as f32 * t).sin() / harmonic as f32
+                })
+                .sum::<f32>();
+            0.5 * (-t / 0.15).exp() * tone
+        })
+        .collect();
+    let spec = WavSpec {
+        format: WavFormat::PCM,
+        channels: 1,
+        sample_rate: sample_rate as u32,
+        bits_per_sample: 16,
+        peak_chunk: false,
+    };
+    let mut writer = WavWriter::new(Cursor::new(vec![]), spec).unwrap();
+    writer.write_samples(&samples).unwrap();
+    let bytes = writer.finalize().unwrap().into_inner();
+    let wav = WavFile::from_bytes(&mut Cursor::new(bytes)).unwrap();
+    let samples = wav.get_mono_samples(ChannelSelection::Downmix).unwrap();
+
+    let options = Options {
+        a4_freq: A4_FREQUENCY,
+        hps_count: 3,
+        analysis_rate: None,
+        method: PitchMethod::Spectrum,
+    };
+    let note = detect_root_note(samples, wav.sample_rate(), &options).unwrap();
+    assert!((note - 57.0).abs() < 0.1, "{}", note);
+}
+
+#[test]
+fn reports_aiff_without_tagging() {
+    let bytes = include_bytes!("../.././A_RECORDING.aif");
+
+    let path = env::temp_dir().join(format!("root_note_{}.aif", std::process::id()));
+    fs::write(&path, bytes).unwrap();
+    let options = Options {
+        a4_freq: A4_FREQUENCY,
+        hps_count: 3,
+        analysis_rate: None,
+        method: PitchMethod::Spectrum,
+    };
+    let result = tag_file(&path, &options);
+    let after = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    result.unwrap();
+    assert_eq!(after, bytes);
+
+    let aiff = AiffFile::from_bytes(&mut Cursor::new(bytes)).unwrap();
+    let samples = aiff.get_mono_samples(ChannelSelection::Downmix).unwrap();
+    let note = detect_root_note(samples, aiff.sample_rate(), &options).unwrap();
+    assert_eq!(note.round() as u32 % 12, 9);
+}
@@ -0,0 +1,62 @@
+//! Reading the id and size that start every chunk of the RIFF and IFF formats.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use byteorder::{ByteOrder, ReadBytesExt};
+
+/// Errors of a format whose streams are made of chunks.
+pub(crate) trait ChunkError: From<io::Error> {
+    /// the stream ended inside the 8 byte header of the chunk at `offset`
+    fn truncated(chunk: [u8; 4], offset: u64) -> Self;
+    /// the chunk at `offset` claims more bytes than are left after its header
+    fn overrun(chunk: [u8; 4], offset: u64, size: u64, available: u64) -> Self;
+}
+
+/// Where a chunk lies in the stream.
+pub(crate) struct ChunkBounds {
+    pub id: [u8; 4],
+    /// stream position of the chunk id
+    pub offset: u64,
+    pub size: u64,
+    /// bytes between the end of the header and the end of the stream
+    pub bytes_left: u64,
+}
+
+/// Reads a chunk id and its 32 bit size, checking the body fits in the stream.
+///
+/// `resolve_size` turns the size field into the real size, for formats such as RF64 that
+/// keep larger sizes elsewhere.
+pub(crate) fn read_chunk_bounds<B: ByteOrder, T: Read + Seek, E: ChunkError>(
+    data: &mut T,
+    resolve_size: impl FnOnce([u8; 4], u64, u32) -> Result<u64, E>,
+) -> Result<ChunkBounds, E> {
+    let offset = data.stream_position()?;
+    let available = bytes_remaining::<E, _>(data)?;
+    let mut id = [0u8; 4];
+    data.read_exact(&mut id[..available.min(4) as usize])?;
+    if available < 8 {
+        return Err(E::truncated(id, offset));
+    }
+    let size = resolve_size(id, offset, data.read_u32::<B>()?)?;
+    let bytes_left = available - 8;
+    if size > bytes_left {
+        return Err(E::overrun(id, offset, size, bytes_left));
+    }
+    Ok(ChunkBounds {
+        id,
+        offset,
+        size,
+        bytes_left,
+    })
+}
+
+pub(crate) fn bytes_remaining<E: From<io::Error>, T: Seek>(data: &mut T) -> Result<u64, E> {
+    let old_pos = data.stream_position()?;
+    let end = data.seek(SeekFrom::End(0))?;
+    // Avoid seeking a third time when we were already at the end of the
+    // stream. The branch is usually way cheaper than a seek operation.
+    if old_pos != end {
+        data.seek(SeekFrom::Start(old_pos))?;
+    }
+    Ok(end - old_pos)
+}
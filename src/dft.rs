@@ -1,13 +1,8 @@
-use std::{
-    f32::consts::{E, PI},
-    io::Cursor,
-};
+use std::f64::consts::PI;
 
-use num_complex::{c32, Complex, ComplexFloat};
+use num_complex::Complex;
 
-use crate::wav::WavFile;
-
-const COMPLEX_E: Complex<f32> = Complex::new(E, 0.0);
+use crate::fft::Float;
 
 #[derive(Debug, Clone, Copy)]
 pub enum TransformType {
@@ -15,60 +10,151 @@ pub enum TransformType {
     Inverse,
 }
 
-pub struct DFT {
-    data: Box<[Complex<f32>]>,
+/// Direct evaluation of the transform at any length, as the reference `FFT` and `FftPlan`
+/// are checked against in `f32` or `f64`.
+///
+/// Every bin is summed in f64 with compensated summation, over twiddles indexed by
+/// `k * n mod N` so no angle is ever larger than 2π. That leaves the result within an ulp
+/// or so of `T` of the exact transform of `data`, but still at O(N²) cost. Like `FFT`, the
+/// forward transform is unscaled and the inverse divides by N.
+pub struct DFT<T = f32> {
+    data: Box<[Complex<T>]>,
     direction: TransformType,
+    // e^(∓2πij/N) for every j below N, the sign following the direction
+    twiddles: Box<[Complex<f64>]>,
 }
 
-impl DFT {
-    pub fn new(
-        data: Box<[Complex<f32>]>,
-        direction: TransformType,
-    ) -> Result<DFT, Box<[Complex<f32>]>> {
-        if !data.len().is_power_of_two() {
-            return Err(data);
-        } else {
-            return Ok(DFT { data, direction });
+impl<T: Float> DFT<T> {
+    pub fn new(data: Box<[Complex<T>]>, direction: TransformType) -> Self {
+        let size = data.len();
+        let sign = match direction {
+            TransformType::Forward => -1.0,
+            TransformType::Inverse => 1.0,
+        };
+        let twiddles = (0..size)
+            .map(|j| Complex::from_polar(1.0, sign * 2.0 * PI * j as f64 / size as f64))
+            .collect();
+        DFT {
+            data,
+            direction,
+            twiddles,
         }
     }
 
-    pub fn transform(self) -> Box<[Complex<f32>]> {
-        match self.direction {
-            TransformType::Forward => Self::forward_transform(self.data),
-            TransformType::Inverse => Self::inverse_transform(self.data),
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Just bin `index` of the transform, for checking a few bins of a long signal.
+    ///
+    /// # Panics
+    /// If `index` isn't below `size()`.
+    pub fn bin(&self, index: usize) -> Complex<T> {
+        let size = self.size();
+        assert!(index < size, "DFT bin {} out of range", index);
+        let (mut re, mut im) = (CompensatedSum::default(), CompensatedSum::default());
+        // the twiddle for sample n is index * n mod size, stepped along without overflowing
+        let mut position = 0;
+        for value in self.data.iter() {
+            let (a, b) = (value.re.to_f64().unwrap(), value.im.to_f64().unwrap());
+            let twiddle = self.twiddles[position];
+            // each product added on its own, so none of them cancel before compensation
+            re.add(a * twiddle.re);
+            re.add(-b * twiddle.im);
+            im.add(a * twiddle.im);
+            im.add(b * twiddle.re);
+            position += index;
+            if position >= size {
+                position -= size;
+            }
         }
+        let scale = match self.direction {
+            TransformType::Forward => 1.0,
+            TransformType::Inverse => size as f64,
+        };
+        Complex::new(
+            T::from_f64(re.total() / scale),
+            T::from_f64(im.total() / scale),
+        )
     }
 
-    fn forward_transform(data: Box<[Complex<f32>]>) -> Box<[Complex<f32>]> {
-        let len: usize = data.len();
-        let samples: Box<[Complex<f32>]> = data;
-        let factor: Complex<f32> = -Complex::I * ((2.0 * PI) / len as f32);
-        let result: Vec<Complex<f32>> = (0..len)
-            .map(|n: usize| {
-                let sum: Complex<f32> = samples
-                    .iter()
-                    .enumerate()
-                    .map(|(k, sample)| sample * COMPLEX_E.powc(factor * n as f32 * k as f32))
-                    .sum();
-                sum
-            })
-            .collect::<Vec<_>>();
-        result.into_boxed_slice()
+    pub fn transform(self) -> Box<[Complex<T>]> {
+        (0..self.size()).map(|index| self.bin(index)).collect()
     }
-    fn inverse_transform(data: Box<[Complex<f32>]>) -> Box<[Complex<f32>]> {
-        let len: usize = data.len();
-        let samples: Box<[Complex<f32>]> = data;
-        let factor: Complex<f32> = Complex::I * ((2.0 * PI) / len as f32);
-        let result: Vec<Complex<f32>> = (0..len)
-            .map(|n: usize| {
-                let sum: Complex<f32> = samples
-                    .iter()
-                    .enumerate()
-                    .map(|(k, sample)| sample * COMPLEX_E.powc(factor * n as f32 * k as f32))
-                    .sum();
-                (1.0 / len as f32) * sum
-            })
-            .collect::<Vec<_>>();
-        result.into_boxed_slice()
+}
+
+// Neumaier's take on Kahan summation, which keeps the low order bits each addition rounds
+// off and adds them back at the end
+#[derive(Debug, Default)]
+struct CompensatedSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl CompensatedSum {
+    fn add(&mut self, value: f64) {
+        let sum = self.sum + value;
+        self.compensation += match self.sum.abs() >= value.abs() {
+            true => (self.sum - sum) + value,
+            false => (value - sum) + self.sum,
+        };
+        self.sum = sum;
+    }
+
+    fn total(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+#[test]
+fn compensated_sum() {
+    // plain summation loses the small values entirely
+    let values = [1e16, 1.0, -1e16, 1.0, 1e-3];
+    let mut sum = CompensatedSum::default();
+    values.iter().for_each(|value| sum.add(*value));
+    assert_eq!(sum.total(), 2.001);
+    assert_ne!(values.iter().sum::<f64>(), 2.001);
+}
+
+#[test]
+fn known_transforms() {
+    for size in [1, 2, 3, 7, 12, 100, 101, 1000] {
+        // a complex exponential at bin m puts all of its energy in that bin
+        for m in [0, size / 3, size - 1] {
+            let tone = (0..size)
+                .map(|n| Complex::from_polar(1.0, 2.0 * PI * ((m * n) % size) as f64 / size as f64))
+                .collect::<Box<[Complex<f64>]>>();
+            let spectrum = DFT::new(tone.clone(), TransformType::Forward).transform();
+            for (k, bin) in spectrum.iter().enumerate() {
+                let expected = if k == m { size as f64 } else { 0.0 };
+                assert!(
+                    (bin - expected).norm() < 1e-13 * size as f64,
+                    "{} at bin {} of {}: {}",
+                    m,
+                    k,
+                    size,
+                    bin
+                );
+            }
+            // and the inverse turns it back
+            let restored = DFT::new(spectrum, TransformType::Inverse).transform();
+            for (a, b) in restored.iter().zip(tone.iter()) {
+                assert!((a - b).norm() < 1e-13);
+            }
+        }
+
+        // an impulse at sample d is a tone at every bin
+        let d = size / 2;
+        let mut impulse = vec![Complex::new(0.0f32, 0.0); size];
+        impulse[d] = Complex::new(1.0, 0.0);
+        let dft = DFT::new(impulse.into_boxed_slice(), TransformType::Forward);
+        for k in 0..size {
+            let angle = -2.0 * PI * ((k * d) % size) as f64 / size as f64;
+            let expected = Complex::from_polar(1.0, angle as f32);
+            assert!((dft.bin(k) - expected).norm() < 1e-6);
+        }
     }
+    assert!(DFT::<f32>::new(Box::default(), TransformType::Forward)
+        .transform()
+        .is_empty());
 }
@@ -1,11 +1,25 @@
-use std::{f32::consts::PI, fmt::Error, io::Cursor, mem};
+use std::{fmt::Error, io::Cursor, mem};
 
 use num_complex::Complex;
 
 use crate::{dft::TransformType, wav::WavFile};
 
-pub struct FFT {
-    data: Box<[Complex<f32>]>,
+mod convolution;
+mod float;
+mod plan;
+#[cfg(feature = "simd")]
+mod simd;
+
+pub use convolution::{autocorrelation, convolve, cross_correlation, OverlapSave};
+pub use float::Float;
+pub use plan::FftPlan;
+
+/// One-off transforms of a single buffer, in `f32` unless built from `f64` samples.
+///
+/// Forward transforms are never scaled. Inverse transforms divide by N when asked to,
+/// and the `ifft` functions always do, so an inverse undoes a forward transform exactly.
+pub struct FFT<T = f32> {
+    data: Box<[Complex<T>]>,
     direction: TransformType,
 }
 
@@ -17,40 +31,101 @@ pub fn lower_power_of_two(n: usize) -> usize {
     }
 }
 
-impl FFT {
-    pub fn new(data: &[f32], direction: TransformType) -> Self {
-        let len = if !data.len().is_power_of_two() {
-            lower_power_of_two(data.len())
-        } else {
-            data.len()
-        };
-
-        let complex = data[0..len]
+impl<T: Float> FFT<T> {
+    pub fn new(data: &[T], direction: TransformType) -> Self {
+        let complex = data
             .iter()
-            .map(|value| Complex::new(*value, 0.0))
-            .collect::<Box<[Complex<f32>]>>();
+            .map(|value| Complex::new(*value, T::zero()))
+            .collect::<Box<[Complex<T>]>>();
         Self {
             data: complex,
             direction: direction,
         }
     }
 
-    pub fn transform(&mut self, scale: bool) -> &mut [Complex<f32>] {
-        Self::rearrange(&mut self.data);
-        Self::in_place_transform(&mut self.data, self.direction, scale);
+    /// Wraps data that is already complex, such as a spectrum to transform back.
+    pub fn from_complex(data: Box<[Complex<T>]>, direction: TransformType) -> Self {
+        Self { data, direction }
+    }
+
+    /// Transforms the data in place and returns it; empty data is left as it is.
+    pub fn transform(&mut self, scale: bool) -> &mut [Complex<T>] {
+        if !self.data.is_empty() {
+            Self::fft(&mut self.data, self.direction, scale)
+                .expect("fft only fails for empty data");
+        }
         &mut self.data
     }
-    pub fn fft(data: &mut [Complex<f32>], direction: TransformType, scale: bool) -> Result<(), ()> {
-        Self::rearrange(data);
-        if !data.len().is_power_of_two() {
-            return Err(());
-        } else {
-            Self::in_place_transform(data, direction, scale);
-            Ok(())
+
+    /// The data, transformed or not.
+    pub fn into_inner(self) -> Box<[Complex<T>]> {
+        self.data
+    }
+
+    /// Transforms data of any length in place through a one-off `FftPlan`. Fails only for
+    /// empty data.
+    pub fn fft(data: &mut [Complex<T>], direction: TransformType, scale: bool) -> Result<(), ()> {
+        FftPlan::new(data.len())
+            .ok_or(())?
+            .process(data, direction, scale);
+        Ok(())
+    }
+
+    /// Turns a spectrum of any length back into the signal `fft` made it from, dividing
+    /// by N. `None` for an empty spectrum.
+    pub fn ifft(spectrum: &[Complex<T>]) -> Option<Box<[Complex<T>]>> {
+        let mut data = Box::<[_]>::from(spectrum);
+        Self::fft(&mut data, TransformType::Inverse, true).ok()?;
+        Some(data)
+    }
+
+    /// Transforms N real samples into only the N/2+1 bins from DC up to Nyquist; the rest
+    /// are their complex conjugates. Runs through a one-off `FftPlan`, which does even
+    /// sizes as an N/2 point complex FFT.
+    ///
+    /// `None` for empty data.
+    pub fn real_fft(data: &[T]) -> Option<Box<[Complex<T>]>> {
+        let plan = FftPlan::new(data.len())?;
+        let mut spectrum = vec![Complex::default(); data.len() / 2 + 1];
+        plan.real_forward(data, &mut spectrum);
+        Some(spectrum.into_boxed_slice())
+    }
+
+    /// Inverse of `real_fft` for an even N: turns N/2+1 bins back into N real samples.
+    ///
+    /// Only the real parts of the DC and Nyquist bins are used. Without `scale` the result
+    /// is N times the original signal, as with the complex transform. `None` for fewer than
+    /// 2 bins.
+    pub fn real_ifft(spectrum: &[Complex<T>], scale: bool) -> Option<Box<[T]>> {
+        match spectrum.len() {
+            0 | 1 => None,
+            bins => Self::real_ifft_with_len(spectrum, 2 * (bins - 1), scale),
         }
     }
 
-    fn in_place_transform(data: &mut [Complex<f32>], direction: TransformType, scale: bool) {
+    /// Inverse of `real_fft` for any N, which the N/2+1 bins can't tell apart from N+1 on
+    /// their own when N is even.
+    ///
+    /// Only the real parts of the DC and (for even N) Nyquist bins are used. `None` if N is
+    /// zero or `spectrum` doesn't hold N/2+1 bins.
+    pub fn real_ifft_with_len(
+        spectrum: &[Complex<T>],
+        len: usize,
+        scale: bool,
+    ) -> Option<Box<[T]>> {
+        if len == 0 || spectrum.len() != len / 2 + 1 {
+            return None;
+        }
+        let plan = FftPlan::new(len)?;
+        let mut output = vec![T::zero(); len];
+        plan.real_inverse(&mut spectrum.to_vec(), &mut output, scale);
+        Some(output.into_boxed_slice())
+    }
+
+    // the radix 2 transform with recurrence twiddles `fft` used before plans, kept to
+    // check plans against
+    #[cfg(test)]
+    fn in_place_transform(data: &mut [Complex<T>], direction: TransformType, scale: bool) {
         let len = data.len();
         let mut step = 1;
         if len & (len - 1) != 0 {
@@ -59,19 +134,19 @@ impl FFT {
         while step < len {
             let jump = step << 1;
             let delta = match direction {
-                TransformType::Forward => -PI / step as f32,
-                TransformType::Inverse => PI / step as f32,
+                TransformType::Forward => -T::PI() / T::from_f64(step as f64),
+                TransformType::Inverse => T::PI() / T::from_f64(step as f64),
             };
-            let sin = (delta * 0.5).sin();
+            let sin = (delta * T::from_f64(0.5)).sin();
 
-            let multiplier = Complex::new(-2.0 * sin * sin, delta.sin());
-            let mut factor = Complex::new(1.0, 0.0);
+            let multiplier = Complex::new(T::from_f64(-2.0) * sin * sin, delta.sin());
+            let mut factor = Complex::new(T::one(), T::zero());
 
             (0..step).for_each(|group| {
                 let mut pair_position = group;
                 while pair_position < len {
                     let match_position = pair_position + step;
-                    let product: Complex<f32> = factor * data[match_position];
+                    let product: Complex<T> = factor * data[match_position];
                     data[match_position] = data[pair_position] - product;
                     data[pair_position] += product;
                     pair_position += jump;
@@ -85,7 +160,8 @@ impl FFT {
             Self::scale(data);
         }
     }
-    fn rearrange<T>(data: &mut [T]) {
+    #[cfg(test)]
+    fn rearrange<V>(data: &mut [V]) {
         let mut target: usize = 0;
         let len: usize = data.len();
         (0..len).for_each(|position| {
@@ -106,23 +182,29 @@ impl FFT {
         });
     }
 
-    fn scale(data: &mut [Complex<f32>]) {
-        let factor = 1.0 / data.len() as f32;
+    #[cfg(test)]
+    fn scale(data: &mut [Complex<T>]) {
+        let factor = T::from_f64(1.0 / data.len() as f64);
         data.iter_mut().for_each(|data| *data *= factor);
     }
-    pub fn freq_table(n: u32, scalar: f32) -> Box<[f32]> {
-        let val = 1.0 / (n as f32 * scalar);
+    pub fn freq_table(n: u32, scalar: T) -> Box<[T]> {
+        let val = (T::from_f64(n as f64) * scalar).recip();
 
         let n_c = (n - 1) / 2 + 1;
         let p1 = 0..n_c as i32;
         let p2 = -((n / 2) as i32)..0;
-        let result = p1.chain(p2).map(|x| x as f32 * val).collect::<Box<[f32]>>();
+        let result = p1
+            .chain(p2)
+            .map(|x| T::from_f64(x as f64) * val)
+            .collect::<Box<[T]>>();
         result
     }
 }
 
 #[test]
 fn fft() {
+    use std::f32::consts::PI;
+
     let test_size: u32 = 128;
     let bin_size: f32 = 2.0;
     let test_freq: f32 = 20.0;
@@ -140,8 +222,14 @@ fn fft() {
             Complex::new(f0, 0.0)
         })
         .collect::<Box<[_]>>();
-    FFT::rearrange(&mut test_data);
+    let mut planned = test_data.clone();
+    FFT::<f32>::rearrange(&mut test_data);
     FFT::in_place_transform(&mut test_data, TransformType::Forward, false);
+    FFT::fft(&mut planned, TransformType::Forward, false).unwrap();
+    assert!(planned
+        .iter()
+        .zip(test_data.iter())
+        .all(|(a, b)| (a - b).norm() < 1e-3));
 
     let norm_sqr = test_data
         .iter()
@@ -166,7 +254,232 @@ fn fft() {
 
 fn rearrange() {
     let mut data = [0, 1, 2, 3, 4, 5, 6, 7];
-    FFT::rearrange::<i32>(&mut data);
+    FFT::<f32>::rearrange(&mut data);
 
     assert_eq!(data, [0, 4, 2, 6, 1, 5, 3, 7])
 }
+
+#[test]
+fn real_fft() {
+    for len in [2, 4, 8, 64, 1024] {
+        let data = (0..len)
+            .map(|i| ((i * 7919) % 31) as f32 / 31.0 - 0.5)
+            .collect::<Box<[f32]>>();
+        let mut full = data
+            .iter()
+            .map(|value| Complex::new(*value, 0.0))
+            .collect::<Box<[_]>>();
+        FFT::fft(&mut full, TransformType::Forward, false).unwrap();
+
+        let bins = FFT::real_fft(&data).unwrap();
+        assert_eq!(bins.len(), len / 2 + 1);
+        for (bin, expected) in bins.iter().zip(full.iter()) {
+            assert!((bin - expected).norm() < 1e-4 * len as f32);
+        }
+
+        let restored = FFT::real_ifft(&bins, true).unwrap();
+        for (sample, expected) in restored.iter().zip(data.iter()) {
+            assert!((sample - expected).abs() < 1e-5);
+        }
+        let unscaled = FFT::real_ifft(&bins, false).unwrap();
+        assert!((unscaled[1] - data[1] * len as f32).abs() < 1e-3);
+    }
+    // odd lengths go through a plan
+    let bins = FFT::real_fft(&[1.0f32; 15]).unwrap();
+    assert_eq!(bins.len(), 8);
+    assert!((bins[0].re - 15.0).abs() < 1e-4);
+    assert!(bins[1..].iter().all(|bin| bin.norm() < 1e-4));
+    assert!(FFT::<f32>::real_fft(&[]).is_none());
+    assert_eq!(
+        FFT::real_ifft(&[Complex::new(6.0, 0.0); 4], true)
+            .unwrap()
+            .len(),
+        6
+    );
+    assert!(FFT::real_ifft(&[Complex::new(1.0, 0.0)], true).is_none());
+}
+
+#[test]
+fn matches_dft() {
+    use crate::dft::DFT;
+
+    // largest difference from an f64 DFT, relative to the largest bin
+    fn error<T: Float>(spectrum: &[Complex<T>], expected: &[Complex<f64>]) -> f64 {
+        let peak = expected.iter().map(|bin| bin.norm()).fold(0.0, f64::max);
+        let widen =
+            |bin: &Complex<T>| Complex::new(bin.re.to_f64().unwrap(), bin.im.to_f64().unwrap());
+        spectrum
+            .iter()
+            .zip(expected)
+            .map(|(bin, expected)| (widen(bin) - expected).norm())
+            .fold(0.0, f64::max)
+            / peak
+    }
+
+    // errors of the DFT, FFT::fft, FFT::real_fft and FftPlan when run in T
+    fn errors<T: Float>(signal: &[f64]) -> [f64; 4] {
+        let narrow = signal.iter().map(|x| T::from_f64(*x)).collect::<Box<[T]>>();
+        let complex = narrow
+            .iter()
+            .map(|x| Complex::new(*x, T::zero()))
+            .collect::<Box<[_]>>();
+        let expected = DFT::new(
+            signal.iter().map(|x| Complex::new(*x, 0.0)).collect(),
+            TransformType::Forward,
+        )
+        .transform();
+
+        let direct = DFT::new(complex.clone(), TransformType::Forward).transform();
+        let mut transformed = complex.clone();
+        FFT::fft(&mut transformed, TransformType::Forward, false).unwrap();
+        let real = FFT::real_fft(&narrow).unwrap();
+        let mut planned = complex;
+        FftPlan::new(signal.len())
+            .unwrap()
+            .process(&mut planned, TransformType::Forward, false);
+
+        let errors = [
+            error(&direct, &expected),
+            error(&transformed, &expected),
+            error(&real, &expected),
+            error(&planned, &expected),
+        ];
+        let epsilon = T::epsilon().to_f64().unwrap();
+        let size = signal.len() as f64;
+        // the reference run in T only rounds its inputs and outputs
+        assert!(errors[0] < epsilon, "{:?}", errors);
+        assert!(
+            errors[1..].iter().all(|error| *error < epsilon * size),
+            "{:?}",
+            errors
+        );
+        errors
+    }
+
+    for size in [8, 64, 512, 2048] {
+        let signal = (0..size)
+            .map(|i| ((i * 7919) % 31) as f64 / 31.0 - 0.5)
+            .collect::<Box<[f64]>>();
+        let single = errors::<f32>(&signal);
+        let double = errors::<f64>(&signal);
+        // with an exact reference, f64 gains about as much as its extra precision
+        for (single, double) in single[1..].iter().zip(&double[1..]) {
+            assert!(double * 1e7 < *single);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn random_signal<T: Float>(len: usize, seed: u64) -> Box<[T]> {
+    // xorshift, mapped onto [-1, 1)
+    let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            T::from_f64((state >> 11) as f64 / (1u64 << 52) as f64 - 1.0)
+        })
+        .collect()
+}
+
+#[test]
+fn round_trip() {
+    fn check<T: Float>(tolerance: f64) {
+        let sizes = (1..=64).chain([100, 127, 128, 360, 1000, 1024, 4096]);
+        for (seed, len) in sizes.enumerate() {
+            let signal = random_signal::<T>(len, seed as u64);
+            let close = |restored: &[T]| {
+                restored.len() == len
+                    && restored
+                        .iter()
+                        .zip(signal.iter())
+                        .all(|(a, b)| (*a - *b).abs().to_f64().unwrap() < tolerance)
+            };
+
+            let mut fft = FFT::new(&signal, TransformType::Forward);
+            fft.transform(false);
+            let mut ifft = FFT::from_complex(fft.into_inner(), TransformType::Inverse);
+            ifft.transform(true);
+            let restored = ifft.into_inner();
+            assert!(restored
+                .iter()
+                .all(|z| z.im.abs().to_f64().unwrap() < tolerance));
+            let restored = restored.iter().map(|z| z.re).collect::<Box<[T]>>();
+            assert!(close(&restored), "complex round trip of {}", len);
+
+            let spectrum = FFT::real_fft(&signal).unwrap();
+            let restored = FFT::real_ifft_with_len(&spectrum, len, true).unwrap();
+            assert!(close(&restored), "real round trip of {}", len);
+            if len % 2 == 0 {
+                assert!(close(&FFT::real_ifft(&spectrum, true).unwrap()));
+            }
+
+            // a spectrum that isn't from a real signal comes back complex
+            let complex = signal
+                .iter()
+                .zip(signal.iter().rev())
+                .map(|(re, im)| Complex::new(*re, *im))
+                .collect::<Box<[_]>>();
+            let mut spectrum = complex.clone();
+            FFT::fft(&mut spectrum, TransformType::Forward, false).unwrap();
+            let restored = FFT::ifft(&spectrum).unwrap();
+            assert!(restored.iter().zip(complex.iter()).all(|(a, b)| (a - b)
+                .norm()
+                .to_f64()
+                .unwrap()
+                < tolerance));
+        }
+    }
+
+    check::<f32>(1e-5);
+    check::<f64>(1e-13);
+    assert!(FFT::<f32>::ifft(&[]).is_none());
+    assert!(FFT::<f32>::new(&[], TransformType::Forward)
+        .transform(true)
+        .is_empty());
+    let bins = [Complex::new(1.0f32, 0.0); 3];
+    assert!(FFT::real_ifft_with_len(&bins, 0, true).is_none());
+    assert!(FFT::real_ifft_with_len(&bins, 6, true).is_none());
+    assert_eq!(FFT::real_ifft_with_len(&bins, 5, true).unwrap().len(), 5);
+}
+
+#[test]
+fn inverse_matches_dft() {
+    use crate::dft::DFT;
+
+    for len in [2, 16, 256] {
+        let spectrum = random_signal::<f64>(2 * len, len as u64)
+            .chunks_exact(2)
+            .map(|pair| Complex::new(pair[0], pair[1]))
+            .collect::<Box<[_]>>();
+        let expected = DFT::new(spectrum.clone(), TransformType::Inverse).transform();
+        let restored = FFT::ifft(&spectrum).unwrap();
+        for (a, b) in restored.iter().zip(expected.iter()) {
+            assert!((a - b).norm() < 1e-12);
+        }
+
+        // unscaled, the inverse is N times bigger
+        let mut unscaled = spectrum.clone();
+        FFT::fft(&mut unscaled, TransformType::Inverse, false).unwrap();
+        for (a, b) in unscaled.iter().zip(expected.iter()) {
+            assert!((a - b * len as f64).norm() < 1e-10);
+        }
+
+        // a Hermitian spectrum is the transform of a real signal
+        let mut hermitian = spectrum[..len / 2 + 1].to_vec();
+        hermitian[0].im = 0.0;
+        hermitian[len / 2].im = 0.0;
+        let full = (0..len)
+            .map(|k| match k <= len / 2 {
+                true => hermitian[k],
+                false => hermitian[len - k].conj(),
+            })
+            .collect::<Box<[_]>>();
+        let expected = DFT::new(full, TransformType::Inverse).transform();
+        let real = FFT::real_ifft(&hermitian, true).unwrap();
+        for (a, b) in real.iter().zip(expected.iter()) {
+            assert!((a - b.re).abs() < 1e-12 && b.im.abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use num_complex::Complex;
+
+use super::FftPlan;
+
+/// Full linear convolution of `a` and `b`, `a.len() + b.len() - 1` samples long, through
+/// one zero padded power of two transform. Empty if either input is.
+pub fn convolve(a: &[f32], b: &[f32]) -> Box<[f32]> {
+    if a.is_empty() || b.is_empty() {
+        return Box::default();
+    }
+    let len = a.len() + b.len() - 1;
+    let plan = FftPlan::new(len.next_power_of_two()).unwrap();
+    let mut spectrum = padded_spectrum(&plan, a);
+    spectrum
+        .iter_mut()
+        .zip(padded_spectrum(&plan, b).iter())
+        .for_each(|(a, b)| *a *= b);
+    let mut output = vec![0.0; plan.size()];
+    plan.real_inverse(&mut spectrum, &mut output, true);
+    output.truncate(len);
+    output.into_boxed_slice()
+}
+
+/// Cross-correlation of `a` against `b` at every lag where they overlap, divided by the
+/// geometric mean of their energies so it stays within ±1.
+///
+/// Index `i` holds lag `i - (b.len() - 1)`, the sum of `a[n + lag] * b[n]`, so a copy of `b`
+/// starting `d` samples into `a` peaks at index `d + b.len() - 1`. All zeros if either
+/// input is silent, and empty if either is empty.
+pub fn cross_correlation(a: &[f32], b: &[f32]) -> Box<[f32]> {
+    let reversed = b.iter().rev().copied().collect::<Box<[f32]>>();
+    let mut correlation = convolve(a, &reversed);
+    let energy = |signal: &[f32]| signal.iter().map(|x| (x * x) as f64).sum::<f64>();
+    let norm = (energy(a) * energy(b)).sqrt();
+    correlation.iter_mut().for_each(|value| match norm > 0.0 {
+        true => *value = (*value as f64 / norm) as f32,
+        false => *value = 0.0,
+    });
+    correlation
+}
+
+/// Autocorrelation of `signal` for lags 0 up to `max_lag`, divided by the lag 0 energy so
+/// it starts at 1. Longer lags overlap fewer samples, so they taper towards 0 even for a
+/// perfectly periodic signal.
+///
+/// `max_lag` is limited to `signal.len() - 1`. All zeros for a silent signal, and empty for
+/// an empty one.
+pub fn autocorrelation(signal: &[f32], max_lag: usize) -> Box<[f32]> {
+    if signal.is_empty() {
+        return Box::default();
+    }
+    let max_lag = max_lag.min(signal.len() - 1);
+    // padded far enough that no lag up to max_lag wraps around
+    let plan = FftPlan::new((signal.len() + max_lag).next_power_of_two()).unwrap();
+    let mut spectrum = padded_spectrum(&plan, signal);
+    spectrum
+        .iter_mut()
+        .for_each(|bin| *bin = Complex::new(bin.norm_sqr(), 0.0));
+    let mut output = vec![0.0; plan.size()];
+    plan.real_inverse(&mut spectrum, &mut output, true);
+    output.truncate(max_lag + 1);
+
+    let energy = output[0];
+    output.iter_mut().for_each(|value| match energy > 0.0 {
+        true => *value /= energy,
+        false => *value = 0.0,
+    });
+    output.into_boxed_slice()
+}
+
+/// Streams a signal through an FIR filter, one FFT per block of input.
+///
+/// Each block transforms the last `filter_len - 1` inputs along with `block_len()` new
+/// ones and keeps only the outputs the circular convolution didn't wrap into. Like
+/// `Resampler`, `process` returns the outputs each call completes and `flush` the rest, so
+/// output sample `n` is always `sum of filter[k] * input[n - k]`.
+pub struct OverlapSave {
+    filter_len: usize,
+    plan: FftPlan,
+    // the filter's transform, already divided by the plan size
+    filter_spectrum: Box<[Complex<f32>]>,
+    // the previous filter_len - 1 inputs, then any new ones short of a whole block
+    input: Vec<f32>,
+    block: Box<[f32]>,
+    spectrum: Box<[Complex<f32>]>,
+    scratch: Box<[Complex<f32>]>,
+    input_count: u64,
+    output_count: u64,
+}
+
+impl OverlapSave {
+    /// Filters in blocks of at least `block_len` new samples; the FFT size is the next power
+    /// of two that also fits the filter, and any room left over goes to longer blocks.
+    ///
+    /// # Panics
+    /// If `filter` is empty or `block_len` is zero.
+    pub fn new(filter: &[f32], block_len: usize) -> Self {
+        assert!(!filter.is_empty(), "FIR filters need at least one tap");
+        assert!(block_len > 0, "overlap-save blocks can't be empty");
+        let plan = FftPlan::new((block_len + filter.len() - 1).next_power_of_two()).unwrap();
+        let scale = 1.0 / plan.size() as f32;
+        let filter_spectrum = padded_spectrum(&plan, filter)
+            .iter()
+            .map(|bin| bin * scale)
+            .collect();
+        let mut overlap_save = Self {
+            filter_len: filter.len(),
+            filter_spectrum,
+            input: vec![],
+            block: vec![0.0; plan.size()].into_boxed_slice(),
+            spectrum: vec![Complex::new(0.0, 0.0); plan.size() / 2 + 1].into_boxed_slice(),
+            scratch: vec![Complex::new(0.0, 0.0); plan.scratch_len()].into_boxed_slice(),
+            plan,
+            input_count: 0,
+            output_count: 0,
+        };
+        overlap_save.reset();
+        overlap_save
+    }
+
+    pub fn filter_len(&self) -> usize {
+        self.filter_len
+    }
+
+    /// New input samples each FFT turns into output.
+    pub fn block_len(&self) -> usize {
+        self.plan.size() - (self.filter_len - 1)
+    }
+
+    /// Forgets all buffered input, ready to filter an unrelated signal.
+    pub fn reset(&mut self) {
+        // silence before the first sample
+        self.input.clear();
+        self.input.resize(self.filter_len - 1, 0.0);
+        self.input_count = 0;
+        self.output_count = 0;
+    }
+
+    /// Filters the next stretch of input, returning the output for every whole block.
+    pub fn process(&mut self, input: &[f32]) -> Box<[f32]> {
+        self.input.extend_from_slice(input);
+        self.input_count += input.len() as u64;
+        let block_len = self.block_len();
+        let mut output = vec![];
+        while self.input.len() >= self.plan.size() {
+            self.block.copy_from_slice(&self.input[..self.plan.size()]);
+            self.plan
+                .real_forward_with_scratch(&self.block, &mut self.spectrum, &mut self.scratch);
+            self.spectrum
+                .iter_mut()
+                .zip(self.filter_spectrum.iter())
+                .for_each(|(bin, filter)| *bin *= filter);
+            self.plan.real_inverse_with_scratch(
+                &mut self.spectrum,
+                &mut self.block,
+                &mut self.scratch,
+                false,
+            );
+            output.extend_from_slice(&self.block[self.filter_len - 1..]);
+            self.input.drain(..block_len);
+        }
+        self.output_count += output.len() as u64;
+        output.into_boxed_slice()
+    }
+
+    /// Filters whatever input is still buffered as if the signal went silent, then resets.
+    ///
+    /// The total output over `process` and `flush` is as long as the input, so the filter's
+    /// tail past the last input sample is left out.
+    pub fn flush(&mut self) -> Box<[f32]> {
+        let remaining = (self.input_count - self.output_count) as usize;
+        let mut output = self.process(&vec![0.0; self.block_len()]).into_vec();
+        output.truncate(remaining);
+        self.reset();
+        output.into_boxed_slice()
+    }
+}
+
+// transform of `signal` zero padded to the plan size
+fn padded_spectrum(plan: &FftPlan, signal: &[f32]) -> Box<[Complex<f32>]> {
+    let mut padded = vec![0.0; plan.size()];
+    padded[..signal.len()].copy_from_slice(signal);
+    let mut spectrum = vec![Complex::new(0.0, 0.0); plan.size() / 2 + 1];
+    plan.real_forward(&padded, &mut spectrum);
+    spectrum.into_boxed_slice()
+}
+
+#[cfg(test)]
+fn direct_convolution(a: &[f32], b: &[f32]) -> Vec<f32> {
+    let mut output = vec![0.0; (a.len() + b.len()).saturating_sub(1)];
+    for (i, a) in a.iter().enumerate() {
+        for (j, b) in b.iter().enumerate() {
+            output[i + j] += a * b;
+        }
+    }
+    output
+}
+
+#[test]
+fn convolution() {
+    let signal = super::random_signal::<f32>(300, 1);
+    for filter_len in [1, 2, 7, 64, 300, 1000] {
+        let filter = super::random_signal::<f32>(filter_len, filter_len as u64);
+        let expected = direct_convolution(&signal, &filter);
+        let convolved = convolve(&signal, &filter);
+        assert_eq!(convolved.len(), expected.len());
+        for (a, b) in convolved.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 1e-4, "{} taps: {} vs {}", filter_len, a, b);
+        }
+    }
+    assert!(convolve(&signal, &[]).is_empty());
+    assert_eq!(&convolve(&[1.0, 2.0], &[1.0])[..], &[1.0, 2.0]);
+}
+
+#[test]
+fn overlap_save() {
+    let signal = super::random_signal::<f32>(5000, 2);
+    for (filter_len, block_len) in [(1, 16), (31, 100), (255, 256), (400, 1)] {
+        let filter = super::random_signal::<f32>(filter_len, 3);
+        let expected = direct_convolution(&signal, &filter);
+
+        let mut overlap_save = OverlapSave::new(&filter, block_len);
+        assert!(overlap_save.block_len() >= block_len);
+        let mut streamed = vec![];
+        let mut rest = &signal[..];
+        for chunk_len in [1, 13, 700, 64, 2].iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at((*chunk_len).min(rest.len()));
+            streamed.extend_from_slice(&overlap_save.process(chunk));
+            rest = tail;
+        }
+        streamed.extend_from_slice(&overlap_save.flush());
+        assert_eq!(streamed.len(), signal.len());
+        for (a, b) in streamed.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 1e-4, "{} taps: {} vs {}", filter_len, a, b);
+        }
+    }
+}
+
+#[test]
+fn correlation() {
+    // b is a stretch of a, 123 samples in
+    let a = super::random_signal::<f32>(1000, 4);
+    let b = &a[123..323];
+    let correlation = cross_correlation(&a, b);
+    assert_eq!(correlation.len(), a.len() + b.len() - 1);
+    let (peak, value) = crate::audio_analysis::find_max_float(&correlation);
+    assert_eq!(peak, 123 + b.len() - 1);
+    let energy = |signal: &[f32]| signal.iter().map(|x| x * x).sum::<f32>();
+    assert!((value - (energy(b) / energy(&a)).sqrt()).abs() < 1e-4);
+    assert!(correlation.iter().all(|value| value.abs() <= 1.0 + 1e-5));
+    assert!(cross_correlation(&a, &[0.0; 10])
+        .iter()
+        .all(|value| *value == 0.0));
+
+    // a sine's autocorrelation peaks again one period later
+    let sine = (0..4800)
+        .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin())
+        .collect::<Box<[f32]>>();
+    let lags = autocorrelation(&sine, 200);
+    assert_eq!(lags.len(), 201);
+    assert!((lags[0] - 1.0).abs() < 1e-6);
+    let (period, _) = crate::audio_analysis::find_max_float(&lags[50..]);
+    assert_eq!(period + 50, 109);
+    for (lag, value) in lags.iter().enumerate() {
+        let expected = sine
+            .iter()
+            .zip(&sine[lag..])
+            .map(|(a, b)| a * b)
+            .sum::<f32>()
+            / sine.iter().map(|x| x * x).sum::<f32>();
+        assert!((value - expected).abs() < 1e-4);
+    }
+    assert_eq!(autocorrelation(&[2.0; 3], 10).len(), 3);
+}
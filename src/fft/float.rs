@@ -0,0 +1,27 @@
+use std::{
+    fmt::{Debug, Display},
+    iter::Sum,
+};
+
+use num_traits::{FloatConst, NumAssign};
+
+/// The sample types transforms run on: `f32` for live analysis, `f64` where long
+/// transforms need the extra precision.
+pub trait Float:
+    num_traits::Float + FloatConst + NumAssign + Sum + Default + Debug + Display + Send + Sync + 'static
+{
+    /// Rounds an `f64` to the nearest value of this type.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Float for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl Float for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
@@ -0,0 +1,682 @@
+use num_complex::Complex;
+
+#[cfg(feature = "simd")]
+use super::simd;
+use super::Float;
+use crate::dft::TransformType;
+
+// sizes with a prime factor above this go through Bluestein's algorithm
+const LARGEST_RADIX: usize = 5;
+
+/// Precomputed tables for FFTs of one size.
+///
+/// Building a plan does all the trigonometry up front, in f64, so transforms don't suffer
+/// from the error a twiddle recurrence builds up over large sizes. Powers of two
+/// use an in-place radix 4 transform, sizes made of 2, 3 and 5 a mixed radix one, and
+/// anything else Bluestein's chirp-z algorithm on a power of two. With the `simd` feature,
+/// `f32` radix 4 passes use AVX or SSE3 when the CPU has them.
+///
+/// A plan for N points also runs N point real transforms. The `_with_scratch` variants run
+/// without allocating given `scratch_len()` values of scratch space; powers of two never
+/// need any.
+///
+/// Plans run in `f32` unless made as `FftPlan::<f64>`, whose error stays far below anything
+/// `f32` samples can resolve, even over the long windows low strings need.
+pub struct FftPlan<T = f32> {
+    size: usize,
+    algorithm: Algorithm<T>,
+    // e^(-2πik/N) for k in 0..N
+    twiddles: Box<[Complex<T>]>,
+    // N/2 point plan the real transforms of even sizes other than powers of two run on
+    half_plan: Option<Box<FftPlan<T>>>,
+    scratch_len: usize,
+}
+
+enum Algorithm<T> {
+    Radix4 {
+        // index i moves to bit_reverse[i] in an N point transform; shifting right by one
+        // gives the N/2 point permutation
+        bit_reverse: Box<[u32]>,
+        // e^(-2πij/m) for j < m/2, for each power of two m from 2 up to N, the table for m
+        // starting at m/2 - 1; passes read their twiddles in order rather than strided
+        stage_twiddles: Box<[Complex<T>]>,
+    },
+    MixedRadix {
+        // radices from the outermost pass in, 4 before 2 before 3 before 5
+        factors: Box<[usize]>,
+    },
+    Bluestein {
+        inner: Box<FftPlan<T>>,
+        // e^(-πik²/N) for k in 0..N
+        chirp: Box<[Complex<T>]>,
+        // transform of the conjugate chirp wrapped around the inner size, divided by it
+        kernel: Box<[Complex<T>]>,
+    },
+}
+
+impl<T: Float> FftPlan<T> {
+    /// `None` for a size of zero or above `u32::MAX`, and for sizes with a prime factor
+    /// above 5 that are too large for Bluestein's algorithm, whose inner transform needs
+    /// a power of two of at least twice the size: any such size above 2^31.
+    pub fn new(size: usize) -> Option<Self> {
+        if size == 0 || size > u32::MAX as usize {
+            return None;
+        }
+        let algorithm = if size.is_power_of_two() {
+            let bits = size.trailing_zeros();
+            let bit_reverse = (0..size as u32)
+                .map(|i| i.reverse_bits().checked_shr(32 - bits).unwrap_or(0))
+                .collect();
+            let stage_twiddles = (1..=bits)
+                .flat_map(|bit| {
+                    let m = 1usize << bit;
+                    (0..m / 2).map(move |j| cis(-2.0 * std::f64::consts::PI * j as f64 / m as f64))
+                })
+                .collect();
+            Algorithm::Radix4 {
+                bit_reverse,
+                stage_twiddles,
+            }
+        } else if let Some(factors) = factorize(size) {
+            Algorithm::MixedRadix { factors }
+        } else {
+            Self::bluestein(size)?
+        };
+        let twiddles = (0..size)
+            .map(|k| {
+                let angle = -2.0 * std::f64::consts::PI * k as f64 / size as f64;
+                cis(angle)
+            })
+            .collect();
+        let half_plan = match size.is_multiple_of(2) && !size.is_power_of_two() {
+            true => Some(Box::new(Self::new(size / 2)?)),
+            false => None,
+        };
+
+        let mut plan = Self {
+            size,
+            algorithm,
+            twiddles,
+            half_plan,
+            scratch_len: 0,
+        };
+        let real_scratch_len = match &plan.half_plan {
+            Some(half_plan) => half_plan.scratch_len,
+            None if size % 2 == 1 => size + plan.complex_scratch_len(),
+            None => 0,
+        };
+        plan.scratch_len = plan.complex_scratch_len().max(real_scratch_len);
+        Some(plan)
+    }
+
+    fn bluestein(size: usize) -> Option<Algorithm<T>> {
+        let inner_size = (2 * size - 1).checked_next_power_of_two()?;
+        let inner = Self::new(inner_size)?;
+        // k² grows past what f64 holds exactly, so reduce it modulo 2N first
+        let chirp = (0..size as u64)
+            .map(|k| {
+                let angle =
+                    -std::f64::consts::PI * ((k * k) % (2 * size as u64)) as f64 / size as f64;
+                cis(angle)
+            })
+            .collect::<Box<[_]>>();
+        let mut kernel = vec![Complex::default(); inner_size];
+        kernel[0] = chirp[0].conj();
+        for k in 1..size {
+            kernel[k] = chirp[k].conj();
+            kernel[inner_size - k] = chirp[k].conj();
+        }
+        inner.process(&mut kernel, TransformType::Forward, true);
+        Some(Algorithm::Bluestein {
+            inner: Box::new(inner),
+            chirp,
+            kernel: kernel.into_boxed_slice(),
+        })
+    }
+
+    fn complex_scratch_len(&self) -> usize {
+        match &self.algorithm {
+            Algorithm::Radix4 { .. } => 0,
+            Algorithm::MixedRadix { .. } => self.size,
+            Algorithm::Bluestein { inner, .. } => inner.size,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Scratch values the `_with_scratch` methods need.
+    pub fn scratch_len(&self) -> usize {
+        self.scratch_len
+    }
+
+    fn scratch(&self) -> Vec<Complex<T>> {
+        vec![Complex::default(); self.scratch_len]
+    }
+
+    /// Transforms `data` in place; `scale` divides by N, as an inverse transform usually needs.
+    ///
+    /// Allocates scratch space for sizes other than powers of two.
+    ///
+    /// # Panics
+    /// If `data` isn't exactly the size the plan was made for.
+    pub fn process(&self, data: &mut [Complex<T>], direction: TransformType, scale: bool) {
+        self.process_with_scratch(data, &mut self.scratch(), direction, scale);
+    }
+
+    /// Like `process`, using `scratch` instead of allocating.
+    ///
+    /// # Panics
+    /// If `data` isn't the plan size or `scratch` is shorter than `scratch_len()`.
+    pub fn process_with_scratch(
+        &self,
+        data: &mut [Complex<T>],
+        scratch: &mut [Complex<T>],
+        direction: TransformType,
+        scale: bool,
+    ) {
+        assert_eq!(data.len(), self.size, "FFT plan used on the wrong size");
+        assert!(scratch.len() >= self.scratch_len, "FFT scratch too short");
+        self.transform(data, scratch, direction);
+        if scale {
+            let factor = T::from_f64(1.0 / self.size as f64);
+            data.iter_mut().for_each(|value| *value *= factor);
+        }
+    }
+
+    /// Transforms N real samples into the N/2+1 bins from DC up to Nyquist.
+    ///
+    /// Allocates scratch space for sizes other than powers of two.
+    ///
+    /// # Panics
+    /// If `input` isn't the plan size or `output` isn't half of it plus one.
+    pub fn real_forward(&self, input: &[T], output: &mut [Complex<T>]) {
+        self.real_forward_with_scratch(input, output, &mut self.scratch());
+    }
+
+    /// Like `real_forward`, using `scratch` instead of allocating.
+    pub fn real_forward_with_scratch(
+        &self,
+        input: &[T],
+        output: &mut [Complex<T>],
+        scratch: &mut [Complex<T>],
+    ) {
+        self.check_real_sizes(input.len(), output.len(), scratch.len());
+        if self.size % 2 == 1 {
+            let (full, scratch) = scratch.split_at_mut(self.size);
+            full.iter_mut()
+                .zip(input)
+                .for_each(|(value, sample)| *value = Complex::new(*sample, T::zero()));
+            self.transform(full, scratch, TransformType::Forward);
+            output.copy_from_slice(&full[..output.len()]);
+            return;
+        }
+
+        let half = self.size / 2;
+        let one_half = T::from_f64(0.5);
+        // even samples in the real part, odd samples in the imaginary part
+        output
+            .iter_mut()
+            .zip(input.chunks_exact(2))
+            .for_each(|(packed, pair)| *packed = Complex::new(pair[0], pair[1]));
+        self.half_transform(&mut output[..half], scratch, TransformType::Forward);
+
+        // untangle the spectra of the even and odd samples, one mirrored pair of bins at a time
+        let z = output[0];
+        output[0] = Complex::new(z.re + z.im, T::zero());
+        output[half] = Complex::new(z.re - z.im, T::zero());
+        for k in 1..=half / 2 {
+            let (z, mirror) = (output[k], output[half - k].conj());
+            let even = (z + mirror) * one_half;
+            let odd = (z - mirror) * Complex::new(T::zero(), -one_half) * self.twiddles[k];
+            output[k] = even + odd;
+            output[half - k] = (even - odd).conj();
+        }
+    }
+
+    /// Turns the N/2+1 bins from `real_forward` back into N real samples.
+    ///
+    /// `spectrum` is used as scratch space and left holding garbage. Only the real parts of
+    /// the DC and (for even sizes) Nyquist bins are used. Without `scale` the result is N
+    /// times the signal. Allocates scratch space for sizes other than powers of two.
+    ///
+    /// # Panics
+    /// If `output` isn't the plan size or `spectrum` isn't half of it plus one.
+    pub fn real_inverse(&self, spectrum: &mut [Complex<T>], output: &mut [T], scale: bool) {
+        self.real_inverse_with_scratch(spectrum, output, &mut self.scratch(), scale);
+    }
+
+    /// Like `real_inverse`, using `scratch` instead of allocating.
+    pub fn real_inverse_with_scratch(
+        &self,
+        spectrum: &mut [Complex<T>],
+        output: &mut [T],
+        scratch: &mut [Complex<T>],
+        scale: bool,
+    ) {
+        self.check_real_sizes(output.len(), spectrum.len(), scratch.len());
+        if self.size % 2 == 1 {
+            let (full, scratch) = scratch.split_at_mut(self.size);
+            full[0] = Complex::new(spectrum[0].re, T::zero());
+            for k in 1..spectrum.len() {
+                full[k] = spectrum[k];
+                full[self.size - k] = spectrum[k].conj();
+            }
+            self.transform(full, scratch, TransformType::Inverse);
+            let factor = T::from_f64(if scale { 1.0 / self.size as f64 } else { 1.0 });
+            output
+                .iter_mut()
+                .zip(full.iter())
+                .for_each(|(sample, value)| *sample = value.re * factor);
+            return;
+        }
+
+        let half = self.size / 2;
+        let one_half = T::from_f64(0.5);
+        let (dc, nyquist) = (spectrum[0].re, spectrum[half].re);
+        spectrum[0] = Complex::new((dc + nyquist) * one_half, (dc - nyquist) * one_half);
+        for k in 1..=half / 2 {
+            let (x, mirror) = (spectrum[k], spectrum[half - k].conj());
+            let even = (x + mirror) * one_half;
+            let odd = (x - mirror) * Complex::new(T::zero(), one_half) * self.twiddles[k].conj();
+            spectrum[k] = even + odd;
+            spectrum[half - k] = (even - odd).conj();
+        }
+        self.half_transform(&mut spectrum[..half], scratch, TransformType::Inverse);
+
+        let factor = T::from_f64(if scale { 1.0 / half as f64 } else { 2.0 });
+        output
+            .chunks_exact_mut(2)
+            .zip(spectrum.iter())
+            .for_each(|(pair, z)| {
+                pair[0] = z.re * factor;
+                pair[1] = z.im * factor;
+            });
+    }
+
+    fn check_real_sizes(&self, samples: usize, bins: usize, scratch: usize) {
+        assert_eq!(samples, self.size, "FFT plan used on the wrong size");
+        assert_eq!(bins, self.size / 2 + 1, "real FFTs have N/2+1 bins");
+        assert!(scratch >= self.scratch_len, "FFT scratch too short");
+    }
+
+    // the N/2 point transform inside an N point real transform
+    fn half_transform(
+        &self,
+        data: &mut [Complex<T>],
+        scratch: &mut [Complex<T>],
+        direction: TransformType,
+    ) {
+        match (&self.half_plan, &self.algorithm) {
+            (Some(half_plan), _) => half_plan.transform(data, scratch, direction),
+            (
+                None,
+                Algorithm::Radix4 {
+                    bit_reverse,
+                    stage_twiddles,
+                },
+            ) => Self::radix_4(data, bit_reverse, stage_twiddles, direction, 1),
+            (None, _) => unreachable!("only powers of two halve without a half plan"),
+        }
+    }
+
+    fn transform(
+        &self,
+        data: &mut [Complex<T>],
+        scratch: &mut [Complex<T>],
+        direction: TransformType,
+    ) {
+        match &self.algorithm {
+            Algorithm::Radix4 {
+                bit_reverse,
+                stage_twiddles,
+            } => Self::radix_4(data, bit_reverse, stage_twiddles, direction, 0),
+            // only forward kernels, so inverses conjugate on the way in and out
+            Algorithm::MixedRadix { factors } => {
+                let input = &mut scratch[..self.size];
+                input.copy_from_slice(data);
+                if let TransformType::Inverse = direction {
+                    input.iter_mut().for_each(|value| *value = value.conj());
+                }
+                self.mixed_radix(data, input, 1, factors);
+                if let TransformType::Inverse = direction {
+                    data.iter_mut().for_each(|value| *value = value.conj());
+                }
+            }
+            Algorithm::Bluestein {
+                inner,
+                chirp,
+                kernel,
+            } => {
+                let conjugate = matches!(direction, TransformType::Inverse);
+                let padded = &mut scratch[..inner.size];
+                for (k, value) in padded.iter_mut().enumerate() {
+                    *value = match data.get(k) {
+                        Some(x) if conjugate => x.conj() * chirp[k],
+                        Some(x) => x * chirp[k],
+                        None => Complex::default(),
+                    };
+                }
+                inner.transform(padded, &mut [], TransformType::Forward);
+                padded
+                    .iter_mut()
+                    .zip(kernel.iter())
+                    .for_each(|(value, kernel)| *value *= kernel);
+                inner.transform(padded, &mut [], TransformType::Inverse);
+                for ((x, value), chirp) in data.iter_mut().zip(padded.iter()).zip(chirp.iter()) {
+                    *x = value * chirp;
+                    if conjugate {
+                        *x = x.conj();
+                    }
+                }
+            }
+        }
+    }
+
+    // bit reversal, then radix 2 passes fused in pairs into radix 4 ones; `shift` is how
+    // many bits shorter data is than the plan
+    fn radix_4(
+        data: &mut [Complex<T>],
+        bit_reverse: &[u32],
+        stage_twiddles: &[Complex<T>],
+        direction: TransformType,
+        shift: u32,
+    ) {
+        for (i, target) in bit_reverse[..data.len()].iter().enumerate() {
+            let target = (*target >> shift) as usize;
+            if target > i {
+                data.swap(i, target);
+            }
+        }
+
+        let inverse = matches!(direction, TransformType::Inverse);
+        let mut quarter = 1;
+        // an odd number of radix 2 passes leaves one over, and its twiddles are all 1
+        if data.len().trailing_zeros() % 2 == 1 {
+            for pair in data.chunks_exact_mut(2) {
+                let (a, b) = (pair[0], pair[1]);
+                pair[0] = a + b;
+                pair[1] = a - b;
+            }
+            quarter = 2;
+        }
+        while quarter < data.len() {
+            // the twiddles of the two radix 2 passes, over 2 and 4 quarters
+            let inner = &stage_twiddles[quarter - 1..2 * quarter - 1];
+            let outer = &stage_twiddles[2 * quarter - 1..3 * quarter - 1];
+            #[cfg(feature = "simd")]
+            let done = simd::radix_4_pass(data, quarter, inner, outer, inverse);
+            #[cfg(not(feature = "simd"))]
+            let done = false;
+            if !done {
+                match inverse {
+                    true => radix_4_pass::<T, true>(data, quarter, inner, outer),
+                    false => radix_4_pass::<T, false>(data, quarter, inner, outer),
+                }
+            }
+            quarter *= 4;
+        }
+    }
+
+    // decimation in time: transforms the output.len() values input[i * stride] into output,
+    // one sub-transform per residue of the first radix, then combines them
+    fn mixed_radix(
+        &self,
+        output: &mut [Complex<T>],
+        input: &[Complex<T>],
+        stride: usize,
+        factors: &[usize],
+    ) {
+        let radix = factors[0];
+        let len = output.len() / radix;
+        if len == 1 {
+            for (i, value) in output.iter_mut().enumerate() {
+                *value = input[i * stride];
+            }
+        } else {
+            for (residue, part) in output.chunks_exact_mut(len).enumerate() {
+                self.mixed_radix(
+                    part,
+                    &input[residue * stride..],
+                    stride * radix,
+                    &factors[1..],
+                );
+            }
+        }
+
+        let twiddle_stride = self.size / output.len();
+        // e^(-2πiq/radix)
+        let root = |q: usize| self.twiddles[q * self.size / radix];
+        let mut values = [Complex::default(); LARGEST_RADIX];
+        for u in 0..len {
+            for (j, value) in values[..radix].iter_mut().enumerate() {
+                *value = output[u + j * len] * self.twiddles[j * u * twiddle_stride];
+            }
+            match radix {
+                2 => {
+                    output[u] = values[0] + values[1];
+                    output[u + len] = values[0] - values[1];
+                }
+                4 => {
+                    let (sum_02, difference_02) = (values[0] + values[2], values[0] - values[2]);
+                    let (sum_13, difference_13) = (values[1] + values[3], values[1] - values[3]);
+                    // multiplying by -i
+                    let rotated = Complex::new(difference_13.im, -difference_13.re);
+                    output[u] = sum_02 + sum_13;
+                    output[u + len] = difference_02 + rotated;
+                    output[u + 2 * len] = sum_02 - sum_13;
+                    output[u + 3 * len] = difference_02 - rotated;
+                }
+                _ => {
+                    for q in 0..radix {
+                        output[u + q * len] = values[..radix]
+                            .iter()
+                            .enumerate()
+                            .map(|(j, value)| value * root(j * q % radix))
+                            .sum();
+                    }
+                }
+            }
+        }
+    }
+}
+
+// two radix 2 passes at once over blocks of four quarters a, b, c, d: a with b and c with d
+// by the inner twiddles, then a with c and b with d by the outer ones, where the second
+// half of the outer twiddles is the first half times ∓i
+pub(super) fn radix_4_pass<T: Float, const INVERSE: bool>(
+    data: &mut [Complex<T>],
+    quarter: usize,
+    inner: &[Complex<T>],
+    outer: &[Complex<T>],
+) {
+    for block in data.chunks_exact_mut(4 * quarter) {
+        let (a, rest) = block.split_at_mut(quarter);
+        let (b, rest) = rest.split_at_mut(quarter);
+        let (c, d) = rest.split_at_mut(quarter);
+        for j in 0..quarter {
+            let (inner, outer) = match INVERSE {
+                true => (inner[j].conj(), outer[j].conj()),
+                false => (inner[j], outer[j]),
+            };
+            let (b1, d1) = (b[j] * inner, d[j] * inner);
+            let (a1, b1) = (a[j] + b1, a[j] - b1);
+            let (c1, d1) = (c[j] + d1, c[j] - d1);
+            let c2 = c1 * outer;
+            let d2 = d1 * outer;
+            let d2 = match INVERSE {
+                true => Complex::new(-d2.im, d2.re),
+                false => Complex::new(d2.im, -d2.re),
+            };
+            a[j] = a1 + c2;
+            c[j] = a1 - c2;
+            b[j] = b1 + d2;
+            d[j] = b1 - d2;
+        }
+    }
+}
+
+// e^(iθ), rounded from f64
+fn cis<T: Float>(angle: f64) -> Complex<T> {
+    Complex::new(T::from_f64(angle.cos()), T::from_f64(angle.sin()))
+}
+
+// radices for sizes built from 2, 3 and 5, pairing up twos into fours
+fn factorize(mut size: usize) -> Option<Box<[usize]>> {
+    let mut factors = vec![];
+    for radix in [4, 2, 3, 5] {
+        while size.is_multiple_of(radix) {
+            factors.push(radix);
+            size /= radix;
+        }
+    }
+    (size == 1).then(|| factors.into_boxed_slice())
+}
+
+#[test]
+fn matches_fft() {
+    use super::FFT;
+
+    for size in [1, 2, 8, 256, 4096] {
+        let plan = FftPlan::new(size).unwrap();
+        let data = (0..size)
+            .map(|i| Complex::new(((i * 37) % 11) as f32 - 5.0, ((i * 13) % 7) as f32))
+            .collect::<Box<[_]>>();
+        for direction in [TransformType::Forward, TransformType::Inverse] {
+            let mut expected = data.clone();
+            FFT::fft(&mut expected, direction, true).unwrap();
+            let mut planned = data.clone();
+            plan.process(&mut planned, direction, true);
+            for (a, b) in planned.iter().zip(expected.iter()) {
+                assert!((a - b).norm() < 1e-4);
+            }
+        }
+
+        if size >= 2 {
+            let real = data.iter().map(|value| value.re).collect::<Box<[f32]>>();
+            let mut spectrum = vec![Complex::new(0.0, 0.0); size / 2 + 1];
+            plan.real_forward(&real, &mut spectrum);
+            for (a, b) in spectrum.iter().zip(FFT::real_fft(&real).unwrap().iter()) {
+                assert!((a - b).norm() < 1e-5 * size as f32 * b.norm().max(1.0));
+            }
+            let mut restored = vec![0.0; size];
+            plan.real_inverse(&mut spectrum, &mut restored, true);
+            for (a, b) in restored.iter().zip(real.iter()) {
+                assert!((a - b).abs() < 1e-4);
+            }
+        }
+    }
+    assert!(FftPlan::<f32>::new(0).is_none());
+}
+
+#[test]
+fn exact_twiddles() {
+    use super::FFT;
+
+    // a cosine that lands exactly on bin 1001 leaves every other bin empty, so whatever
+    // shows up there is rounding error
+    let size = 1 << 18;
+    let signal = (0..size)
+        .map(|i| {
+            let phase = 2.0 * std::f64::consts::PI * 1001.0 * i as f64 / size as f64;
+            Complex::new(phase.cos() as f32, 0.0)
+        })
+        .collect::<Box<[_]>>();
+    let leakage = |spectrum: &[Complex<f32>]| {
+        spectrum
+            .iter()
+            .enumerate()
+            .filter(|(bin, _)| *bin != 1001 && *bin != size - 1001)
+            .map(|(_, value)| value.norm())
+            .fold(0.0f32, f32::max)
+    };
+
+    let mut recurrence = signal.clone();
+    FFT::<f32>::rearrange(&mut recurrence);
+    FFT::in_place_transform(&mut recurrence, TransformType::Forward, false);
+    let mut planned = signal.clone();
+    FftPlan::new(size)
+        .unwrap()
+        .process(&mut planned, TransformType::Forward, false);
+    assert!(leakage(&planned) < leakage(&recurrence));
+    assert!(leakage(&planned) < 0.05);
+    // one-off transforms get the same tables
+    let mut one_off = signal.clone();
+    FFT::fft(&mut one_off, TransformType::Forward, false).unwrap();
+    assert_eq!(leakage(&one_off), leakage(&planned));
+}
+
+#[test]
+fn any_size() {
+    use crate::dft::DFT;
+
+    // the reference, in f64
+    fn dft(data: &[Complex<f32>]) -> Box<[Complex<f64>]> {
+        let widened = data
+            .iter()
+            .map(|value| Complex::new(value.re as f64, value.im as f64))
+            .collect();
+        DFT::new(widened, TransformType::Forward).transform()
+    }
+
+    // mixed radix sizes, then primes and sizes with a large prime factor for Bluestein
+    for size in [
+        3, 5, 6, 9, 10, 12, 15, 20, 30, 45, 100, 360, 7, 11, 13, 97, 14, 202, 1009,
+    ] {
+        let plan = FftPlan::new(size).unwrap();
+        let data = (0..size)
+            .map(|i| Complex::new(((i * 37) % 11) as f32 - 5.0, ((i * 13) % 7) as f32))
+            .collect::<Box<[_]>>();
+        let expected = dft(&data);
+        let tolerance = 1e-5 * size as f64 * 8.0;
+
+        let mut transformed = data.clone();
+        let mut scratch = vec![Complex::new(0.0, 0.0); plan.scratch_len()];
+        plan.process_with_scratch(
+            &mut transformed,
+            &mut scratch,
+            TransformType::Forward,
+            false,
+        );
+        for (a, b) in transformed.iter().zip(expected.iter()) {
+            let a = Complex::new(a.re as f64, a.im as f64);
+            assert!((a - b).norm() < tolerance, "size {}: {} vs {}", size, a, b);
+        }
+        plan.process(&mut transformed, TransformType::Inverse, true);
+        for (a, b) in transformed.iter().zip(data.iter()) {
+            assert!((a - b).norm() < 1e-4, "size {}", size);
+        }
+
+        let real = data.iter().map(|value| value.re).collect::<Box<[f32]>>();
+        let expected = dft(&real
+            .iter()
+            .map(|value| Complex::new(*value, 0.0))
+            .collect::<Box<[_]>>());
+        let mut spectrum = vec![Complex::new(0.0, 0.0); size / 2 + 1];
+        plan.real_forward_with_scratch(&real, &mut spectrum, &mut scratch);
+        for (a, b) in spectrum.iter().zip(expected.iter()) {
+            let a = Complex::new(a.re as f64, a.im as f64);
+            assert!(
+                (a - b).norm() < tolerance,
+                "real size {}: {} vs {}",
+                size,
+                a,
+                b
+            );
+        }
+        let mut restored = vec![0.0; size];
+        plan.real_inverse_with_scratch(&mut spectrum, &mut restored, &mut scratch, true);
+        for (a, b) in restored.iter().zip(real.iter()) {
+            assert!((a - b).abs() < 1e-4, "real size {}", size);
+        }
+    }
+}
+
+#[test]
+fn size_limits() {
+    assert!(FftPlan::<f32>::new(0).is_none());
+    assert!(FftPlan::<f32>::new(u32::MAX as usize + 1).is_none());
+    // 2^31 + 1 = 3 × 715827883 needs a 2^33 point inner transform
+    assert!(FftPlan::<f32>::new((1 << 31) + 1).is_none());
+}
@@ -0,0 +1,398 @@
+use std::io::Read;
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::wav::{deinterleave, ChannelSelection};
+
+mod bits;
+mod error;
+mod frame;
+mod md5;
+
+pub use error::FlacError;
+
+const FLAC_SIGNATURE: [u8; 4] = [0x66, 0x4C, 0x61, 0x43]; //fLaC
+
+const STREAMINFO_BLOCK: u8 = 0;
+const STREAMINFO_SIZE: usize = 34;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StreamInfo {
+    max_block_size: u16,
+    sample_rate: u32,
+    channels: u16,
+    bits_per_sample: u16,
+    /// samples per channel, 0 when the encoder didn't know
+    total_samples: u64,
+    /// of the decoded samples, all zero when the encoder didn't compute it
+    md5: [u8; 16],
+}
+
+impl StreamInfo {
+    fn parse(body: &[u8], offset: u64) -> Result<Self, FlacError> {
+        let invalid = |reason| FlacError::InvalidMetadata { offset, reason };
+        if body.len() < STREAMINFO_SIZE {
+            return Err(invalid("STREAMINFO shorter than 34 bytes"));
+        }
+        let min_block_size = BigEndian::read_u16(&body[0..2]);
+        let max_block_size = BigEndian::read_u16(&body[2..4]);
+        // 20 bit rate, 3 bit channels - 1, 5 bit bits per sample - 1, 36 bit sample count
+        let packed = BigEndian::read_u64(&body[10..18]);
+        let sample_rate = (packed >> 44) as u32;
+        let channels = ((packed >> 41) & 0x7) as u16 + 1;
+        let bits_per_sample = ((packed >> 36) & 0x1F) as u16 + 1;
+        let total_samples = packed & 0xF_FFFF_FFFF;
+        let mut md5 = [0u8; 16];
+        md5.copy_from_slice(&body[18..34]);
+
+        if min_block_size < 16 || max_block_size < min_block_size {
+            return Err(invalid("block sizes out of range"));
+        }
+        if sample_rate == 0 {
+            return Err(invalid("sample rate of zero"));
+        }
+        if bits_per_sample < 4 {
+            return Err(invalid("fewer than 4 bits per sample"));
+        }
+        Ok(Self {
+            max_block_size,
+            sample_rate,
+            channels,
+            bits_per_sample,
+            total_samples,
+            md5,
+        })
+    }
+}
+
+/// A FLAC stream, decoded up front to the same interleaved f32 samples as `WavFile`.
+#[derive(Debug)]
+pub struct FlacFile {
+    stream_info: StreamInfo,
+    samples: Box<[i32]>,
+}
+
+impl FlacFile {
+    /// Decodes every frame, checking the frame CRCs and, when present, the MD5 signature.
+    pub fn from_bytes<T: Read>(data: &mut T) -> Result<Self, FlacError> {
+        let mut bytes = vec![];
+        data.read_to_end(&mut bytes)?;
+
+        let mut signature = [0u8; 4];
+        let len = bytes.len().min(4);
+        signature[..len].copy_from_slice(&bytes[..len]);
+        if signature != FLAC_SIGNATURE {
+            return Err(FlacError::BadSignature(signature));
+        }
+
+        let mut position = 4;
+        let mut stream_info = None;
+        loop {
+            let offset = position as u64;
+            let header = bytes
+                .get(position..position + 4)
+                .ok_or(FlacError::Truncated { offset })?;
+            let last = header[0] & 0x80 != 0;
+            let block_type = header[0] & 0x7F;
+            let size = BigEndian::read_u24(&header[1..4]) as usize;
+            let body = bytes
+                .get(position + 4..position + 4 + size)
+                .ok_or(FlacError::Truncated { offset })?;
+            match (block_type, &stream_info) {
+                (STREAMINFO_BLOCK, None) if position == 4 => {
+                    stream_info = Some(StreamInfo::parse(body, offset)?)
+                }
+                (STREAMINFO_BLOCK, _) => {
+                    return Err(FlacError::InvalidMetadata {
+                        offset,
+                        reason: "more than one STREAMINFO block",
+                    })
+                }
+                (127, _) => {
+                    return Err(FlacError::InvalidMetadata {
+                        offset,
+                        reason: "invalid metadata block type",
+                    })
+                }
+                (_, None) => return Err(FlacError::MissingStreamInfo),
+                // SEEKTABLE, VORBIS_COMMENT, PICTURE, ... are not needed for decoding
+                _ => {}
+            }
+            position += 4 + size;
+            if last {
+                break;
+            }
+        }
+        let stream_info = stream_info.ok_or(FlacError::MissingStreamInfo)?;
+
+        let channels = stream_info.channels as usize;
+        let expected = stream_info.total_samples as usize * channels;
+        let mut samples = vec![];
+        // without a sample count the frames end at the first bytes that can't start one,
+        // such as an ID3v1 tag some taggers append
+        while position < bytes.len()
+            && match expected {
+                0 => frame::has_sync_code(&bytes[position..]),
+                expected => samples.len() < expected,
+            }
+        {
+            position += frame::decode_frame(
+                &bytes[position..],
+                position as u64,
+                &stream_info,
+                &mut samples,
+            )?;
+        }
+        if samples.len() < expected {
+            return Err(FlacError::Truncated {
+                offset: position as u64,
+            });
+        }
+        if expected > 0 {
+            samples.truncate(expected);
+        }
+
+        if stream_info.md5 != [0; 16] {
+            let actual = audio_md5(&samples, stream_info.bits_per_sample);
+            if actual != stream_info.md5 {
+                return Err(FlacError::Md5Mismatch {
+                    expected: stream_info.md5,
+                    actual,
+                });
+            }
+        }
+        Ok(Self {
+            stream_info,
+            samples: samples.into_boxed_slice(),
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.stream_info.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.stream_info.channels
+    }
+
+    pub fn bits_per_sample(&self) -> u16 {
+        self.stream_info.bits_per_sample
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        (self.samples.len() / self.stream_info.channels as usize) as u64
+    }
+
+    /// The decoded integer samples, interleaved, exactly as the encoder was given them.
+    pub fn raw_samples(&self) -> &[i32] {
+        &self.samples
+    }
+
+    /// The decoded samples interleaved and normalized to `[-1.0, 1.0)`.
+    pub fn get_samples(&self) -> Result<Box<[f32]>, FlacError> {
+        let scale = 1.0 / (1u64 << (self.stream_info.bits_per_sample - 1)) as f64;
+        Ok(self
+            .samples
+            .iter()
+            .map(|sample| (*sample as f64 * scale) as f32)
+            .collect())
+    }
+
+    /// Decodes the samples into one buffer per channel.
+    pub fn get_channels(&self) -> Result<Box<[Box<[f32]>]>, FlacError> {
+        let channels = self.stream_info.channels;
+        deinterleave(&self.get_samples()?, channels).map_err(|_| FlacError::InvalidChannel {
+            channel: 0,
+            channels,
+        })
+    }
+
+    /// Decodes the samples down to the single signal picked by `selection`.
+    pub fn get_mono_samples(&self, selection: ChannelSelection) -> Result<Box<[f32]>, FlacError> {
+        let channels = self.stream_info.channels;
+        match selection {
+            ChannelSelection::Channel(channel) if channel >= channels => {
+                Err(FlacError::InvalidChannel { channel, channels })
+            }
+            _ => selection
+                .apply(&self.get_samples()?, channels)
+                .map_err(|_| FlacError::InvalidChannel {
+                    channel: 0,
+                    channels,
+                }),
+        }
+    }
+}
+
+// the signature covers each sample as a little endian integer of just enough whole bytes
+fn audio_md5(samples: &[i32], bits_per_sample: u16) -> [u8; 16] {
+    let width = (bits_per_sample as usize).div_ceil(8);
+    let mut md5 = md5::Md5::new();
+    let mut buffer = Vec::with_capacity(4096 * width);
+    for block in samples.chunks(4096) {
+        buffer.clear();
+        for sample in block {
+            buffer.extend_from_slice(&sample.to_le_bytes()[..width]);
+        }
+        md5.update(&buffer);
+    }
+    md5.finalize()
+}
+
+#[cfg(test)]
+fn quantize(samples: &[f32], bits_per_sample: u16) -> Vec<i32> {
+    let scale = (1i64 << (bits_per_sample - 1)) as f64;
+    samples
+        .iter()
+        .map(|sample| (*sample as f64 * scale).round().clamp(-scale, scale - 1.0) as i32)
+        .collect()
+}
+
+#[test]
+fn decodes_like_wav() {
+    use crate::{
+        audio_analysis::{AudioAnalyzer, Note, WindowType},
+        wav::WavFile,
+    };
+    use std::io::Cursor;
+
+    let wav = WavFile::from_bytes(&mut Cursor::new(include_bytes!(".././A.wav"))).unwrap();
+    let flac = FlacFile::from_bytes(&mut &include_bytes!(".././A.flac")[..]).unwrap();
+    assert_eq!(flac.sample_rate(), 48000);
+    assert_eq!((flac.channels(), flac.bits_per_sample()), (1, 16));
+    assert_eq!(flac.frame_count(), wav.frame_count());
+    assert_eq!(
+        flac.raw_samples(),
+        &quantize(&wav.get_samples().unwrap(), 16)[..]
+    );
+
+    let mut analyzer =
+        AudioAnalyzer::new(flac.sample_rate(), 1024 * 50, 0, 3, 440, WindowType::Hann);
+    analyzer.add_samples(&flac.get_samples().unwrap());
+    assert_eq!(Note::from_frequency(analyzer.strongest_freq()), Note::A);
+}
+
+#[test]
+fn stereo_decorrelation() {
+    use crate::{
+        audio_analysis::{AudioAnalyzer, Note, WindowType},
+        wav::WavFile,
+    };
+    use std::io::Cursor;
+
+    // A4 on the left and B4 on the right, coded as left/side, side/right and mid/side frames
+    let flac = FlacFile::from_bytes(&mut &include_bytes!(".././A_B_24BIT.flac")[..]).unwrap();
+    assert_eq!((flac.channels(), flac.bits_per_sample()), (2, 24));
+    let channels = flac.get_channels().unwrap();
+    for (channel, file) in [
+        (0, &include_bytes!(".././A.wav")[..]),
+        (1, &include_bytes!(".././B.wav")[..]),
+    ] {
+        let wav = WavFile::from_bytes(&mut Cursor::new(file)).unwrap();
+        let expected = quantize(&wav.get_samples().unwrap()[..24000], 24);
+        let decoded = flac
+            .raw_samples()
+            .iter()
+            .skip(channel)
+            .step_by(2)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, expected);
+        assert_eq!(channels[channel].len(), 24000);
+    }
+
+    for (channel, note) in [(0, Note::A), (1, Note::B)] {
+        let mut analyzer = AudioAnalyzer::new(48000, 1024 * 16, 0, 3, 440, WindowType::Hann);
+        analyzer.add_samples(
+            &flac
+                .get_mono_samples(ChannelSelection::Channel(channel))
+                .unwrap(),
+        );
+        assert_eq!(Note::from_frequency(analyzer.strongest_freq()), note);
+    }
+    assert!(matches!(
+        flac.get_mono_samples(ChannelSelection::Channel(2)),
+        Err(FlacError::InvalidChannel {
+            channel: 2,
+            channels: 2
+        })
+    ));
+}
+
+#[test]
+fn md5_signature() {
+    let base = include_bytes!(".././A.flac").to_vec();
+    // the signature is the last 16 bytes of STREAMINFO
+    let signature = 8 + STREAMINFO_SIZE - 16;
+
+    let mut bytes = base.clone();
+    bytes[signature] ^= 1;
+    assert!(matches!(
+        FlacFile::from_bytes(&mut &bytes[..]),
+        Err(FlacError::Md5Mismatch { .. })
+    ));
+
+    // encoders that don't compute a signature leave it zeroed
+    let mut bytes = base.clone();
+    bytes[signature..signature + 16].fill(0);
+    assert!(FlacFile::from_bytes(&mut &bytes[..]).is_ok());
+}
+
+#[test]
+fn unknown_length() {
+    let base = include_bytes!(".././A.flac");
+    let expected = FlacFile::from_bytes(&mut &base[..]).unwrap();
+
+    // the 36 bit sample count ends STREAMINFO's packed rate, channels and sizes
+    let mut bytes = base.to_vec();
+    let packed = 8 + 10;
+    bytes[packed + 3] &= 0xF0;
+    bytes[packed + 4..packed + 8].fill(0);
+    let mut tag = b"TAG".to_vec();
+    tag.resize(128, b' ');
+    bytes.extend_from_slice(&tag);
+
+    let flac = FlacFile::from_bytes(&mut &bytes[..]).unwrap();
+    assert_eq!(flac.raw_samples(), expected.raw_samples());
+}
+
+#[test]
+fn corrupt_streams() {
+    let base = include_bytes!(".././A_B_24BIT.flac");
+    let mut corpus = vec![];
+    // the header, the metadata and the start of the first frame, then every 97th length
+    for len in (0..200).chain((200..base.len()).step_by(97)) {
+        corpus.push(base[..len].to_vec());
+    }
+    for position in 0..400 {
+        for value in [0x00, 0x01, 0x7F, 0x80, 0xFF] {
+            let mut mutated = base.to_vec();
+            mutated[position] = value;
+            corpus.push(mutated);
+        }
+    }
+    for bytes in corpus {
+        if let Ok(flac) = FlacFile::from_bytes(&mut &bytes[..]) {
+            let _ = flac.get_channels();
+            let _ = flac.get_mono_samples(ChannelSelection::Downmix);
+        }
+    }
+
+    assert!(matches!(
+        FlacFile::from_bytes(&mut &base[..base.len() - 1000]),
+        Err(FlacError::Truncated { .. })
+    ));
+    // first byte of the first subframe
+    let mut bytes = base.to_vec();
+    bytes[8 + STREAMINFO_SIZE + 12] ^= 0x10;
+    assert!(matches!(
+        FlacFile::from_bytes(&mut &bytes[..]),
+        Err(FlacError::CrcMismatch { offset: 42 } | FlacError::InvalidFrame { offset: 42, .. })
+    ));
+    let mut bytes = base.to_vec();
+    bytes[4] = 0x84;
+    assert!(matches!(
+        FlacFile::from_bytes(&mut &bytes[..]),
+        Err(FlacError::MissingStreamInfo)
+    ));
+}
@@ -0,0 +1,174 @@
+use super::FlacError;
+
+/// Reads big endian bit fields from a frame, reporting running out of data as truncation.
+pub(super) struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize, // in bits
+    // stream offset of `data[0]`, for error messages
+    offset: u64,
+}
+
+impl<'a> BitReader<'a> {
+    pub(super) fn new(data: &'a [u8], offset: u64) -> Self {
+        Self {
+            data,
+            position: 0,
+            offset,
+        }
+    }
+
+    fn truncated(&self) -> FlacError {
+        FlacError::Truncated {
+            offset: self.offset,
+        }
+    }
+
+    pub(super) fn invalid(&self, reason: &'static str) -> FlacError {
+        FlacError::InvalidFrame {
+            offset: self.offset,
+            reason,
+        }
+    }
+
+    /// Bytes consumed so far, counting a partly read byte as consumed.
+    pub(super) fn byte_position(&self) -> usize {
+        self.position.div_ceil(8)
+    }
+
+    /// Skips to the next byte boundary.
+    pub(super) fn align(&mut self) {
+        self.position = self.byte_position() * 8;
+    }
+
+    /// Reads `count` (at most 64) bits as an unsigned number.
+    pub(super) fn read_bits(&mut self, count: u32) -> Result<u64, FlacError> {
+        if self.position + count as usize > self.data.len() * 8 {
+            return Err(self.truncated());
+        }
+        let mut value = 0u64;
+        let mut remaining = count;
+        while remaining > 0 {
+            let bit_offset = (self.position % 8) as u32;
+            let available = 8 - bit_offset;
+            let take = available.min(remaining);
+            let byte = self.data[self.position / 8] as u64;
+            let bits = (byte >> (available - take)) & ((1 << take) - 1);
+            value = if take == 64 {
+                bits
+            } else {
+                (value << take) | bits
+            };
+            remaining -= take;
+            self.position += take as usize;
+        }
+        Ok(value)
+    }
+
+    /// Reads `count` (at most 64) bits as a two's complement number.
+    pub(super) fn read_signed(&mut self, count: u32) -> Result<i64, FlacError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let shift = 64 - count;
+        Ok(((self.read_bits(count)? << shift) as i64) >> shift)
+    }
+
+    pub(super) fn read_bit(&mut self) -> Result<bool, FlacError> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    /// Counts zero bits up to and including the next one bit.
+    pub(super) fn read_unary(&mut self) -> Result<u64, FlacError> {
+        let mut zeros = 0;
+        loop {
+            let byte = *self
+                .data
+                .get(self.position / 8)
+                .ok_or_else(|| self.truncated())?;
+            let bit_offset = self.position % 8;
+            let bits = byte << bit_offset;
+            if bits == 0 {
+                zeros += (8 - bit_offset) as u64;
+                self.position += 8 - bit_offset;
+            } else {
+                let leading = bits.leading_zeros() as usize;
+                zeros += leading as u64;
+                self.position += leading + 1;
+                return Ok(zeros);
+            }
+        }
+    }
+
+    /// Reads a Rice coded, zigzag mapped residual with parameter `parameter`.
+    pub(super) fn read_rice(&mut self, parameter: u32) -> Result<i64, FlacError> {
+        let quotient = self.read_unary()?;
+        let remainder = self.read_bits(parameter)?;
+        let folded = quotient.wrapping_shl(parameter) | remainder;
+        Ok((folded >> 1) as i64 ^ -((folded & 1) as i64))
+    }
+}
+
+const fn crc_table<const WIDTH: u32>(polynomial: u16) -> [u16; 256] {
+    let top = 1u32 << (WIDTH - 1);
+    let mask = ((1u32 << WIDTH) - 1) as u16;
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << (WIDTH - 8);
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & top != 0 {
+                (crc << 1) ^ polynomial as u32
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc as u16 & mask;
+        i += 1;
+    }
+    table
+}
+
+const CRC8_TABLE: [u16; 256] = crc_table::<8>(0x07);
+const CRC16_TABLE: [u16; 256] = crc_table::<16>(0x8005);
+
+/// CRC-8 (polynomial x^8 + x^2 + x + 1) protecting frame headers.
+pub(super) fn crc8(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0u8, |crc, byte| CRC8_TABLE[(crc ^ byte) as usize] as u8)
+}
+
+/// CRC-16 (polynomial x^16 + x^15 + x^2 + 1) protecting whole frames.
+pub(super) fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, byte| {
+        (crc << 8) ^ CRC16_TABLE[((crc >> 8) as u8 ^ byte) as usize]
+    })
+}
+
+#[test]
+fn bit_fields() {
+    let data = [0b1011_1000, 0b0000_0001, 0xFF, 0x80];
+    let mut bits = BitReader::new(&data, 0);
+    assert_eq!(bits.read_bits(3).unwrap(), 0b101);
+    assert_eq!(bits.read_signed(3).unwrap(), -2);
+    assert_eq!(bits.read_unary().unwrap(), 9);
+    assert_eq!(bits.read_bits(9).unwrap(), 0x1FF);
+    bits.align();
+    assert_eq!(bits.byte_position(), 4);
+    assert!(bits.read_bit().is_err());
+
+    // zigzag 0, -1, 1, -2 with parameter 1: 1|0 1|1 01|0 01|1
+    let mut bits = BitReader::new(&[0b1011_0100, 0b1100_0000], 0);
+    let residuals = (0..4)
+        .map(|_| bits.read_rice(1).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(residuals, [0, -1, 1, -2]);
+}
+
+#[test]
+fn crcs() {
+    // check values for "123456789"
+    assert_eq!(crc8(b"123456789"), 0xF4);
+    assert_eq!(crc16(b"123456789"), 0xFEE8);
+}
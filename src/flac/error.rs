@@ -0,0 +1,97 @@
+use std::{error::Error, fmt, io};
+
+/// Everything that can go wrong decoding a FLAC stream.
+///
+/// `offset` fields are byte offsets from the start of the stream to the metadata
+/// block or frame the problem was found in.
+#[derive(Debug)]
+pub enum FlacError {
+    Io(io::Error),
+    /// the stream does not start with fLaC
+    BadSignature([u8; 4]),
+    /// the first metadata block is not STREAMINFO
+    MissingStreamInfo,
+    /// a metadata block whose contents contradict themselves
+    InvalidMetadata {
+        offset: u64,
+        reason: &'static str,
+    },
+    /// a metadata block or frame that runs past the end of the stream
+    Truncated {
+        offset: u64,
+    },
+    /// a frame that breaks the format, or disagrees with STREAMINFO
+    InvalidFrame {
+        offset: u64,
+        reason: &'static str,
+    },
+    /// the CRC-8 of a frame header or the CRC-16 of a whole frame doesn't match
+    CrcMismatch {
+        offset: u64,
+    },
+    /// the decoded audio doesn't match the MD5 signature in STREAMINFO
+    Md5Mismatch {
+        expected: [u8; 16],
+        actual: [u8; 16],
+    },
+    /// a channel index at or above the channel count
+    InvalidChannel {
+        channel: u16,
+        channels: u16,
+    },
+}
+
+impl fmt::Display for FlacError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlacError::Io(error) => write!(f, "io error: {}", error),
+            FlacError::BadSignature(found) => {
+                write!(f, "bad FLAC signature {:?}", String::from_utf8_lossy(found))
+            }
+            FlacError::MissingStreamInfo => write!(f, "No STREAMINFO block"),
+            FlacError::InvalidMetadata { offset, reason } => {
+                write!(
+                    f,
+                    "metadata block at byte {} is invalid: {}",
+                    offset, reason
+                )
+            }
+            FlacError::Truncated { offset } => write!(
+                f,
+                "stream ends inside the block or frame at byte {}",
+                offset
+            ),
+            FlacError::InvalidFrame { offset, reason } => {
+                write!(f, "frame at byte {} is invalid: {}", offset, reason)
+            }
+            FlacError::CrcMismatch { offset } => {
+                write!(f, "frame at byte {} fails its CRC check", offset)
+            }
+            FlacError::Md5Mismatch { expected, actual } => write!(
+                f,
+                "decoded audio has MD5 {:02x?} but STREAMINFO says {:02x?}",
+                actual, expected
+            ),
+            FlacError::InvalidChannel { channel, channels } => write!(
+                f,
+                "channel {} out of range for {} channels",
+                channel, channels
+            ),
+        }
+    }
+}
+
+impl Error for FlacError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FlacError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FlacError {
+    fn from(error: io::Error) -> Self {
+        FlacError::Io(error)
+    }
+}
@@ -0,0 +1,301 @@
+use super::{
+    bits::{crc16, crc8, BitReader},
+    FlacError, StreamInfo,
+};
+
+const SYNC_CODE: u64 = 0b11_1111_1111_1110;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChannelAssignment {
+    Independent(u16),
+    LeftSide,
+    SideRight,
+    MidSide,
+}
+
+/// Whether `data` starts with a frame sync code and the reserved bit after it.
+pub(super) fn has_sync_code(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] == 0xFF && data[1] & 0xFE == 0xF8
+}
+
+/// Decodes the frame at the start of `data`, appending its interleaved samples to `out`.
+///
+/// Returns the number of bytes the frame took up. `offset` is the stream position of
+/// `data[0]`, for error messages.
+pub(super) fn decode_frame(
+    data: &[u8],
+    offset: u64,
+    info: &StreamInfo,
+    out: &mut Vec<i32>,
+) -> Result<usize, FlacError> {
+    let mut bits = BitReader::new(data, offset);
+    let invalid = |reason| FlacError::InvalidFrame { offset, reason };
+
+    if bits.read_bits(14)? != SYNC_CODE {
+        return Err(invalid("missing frame sync code"));
+    }
+    if bits.read_bit()? {
+        return Err(invalid("reserved header bit set"));
+    }
+    let _variable_block_size = bits.read_bit()?;
+    let block_size_code = bits.read_bits(4)?;
+    let sample_rate_code = bits.read_bits(4)?;
+    let channels = match bits.read_bits(4)? {
+        code @ 0..=7 => ChannelAssignment::Independent(code as u16 + 1),
+        8 => ChannelAssignment::LeftSide,
+        9 => ChannelAssignment::SideRight,
+        10 => ChannelAssignment::MidSide,
+        _ => return Err(invalid("reserved channel assignment")),
+    };
+    let bits_per_sample = match bits.read_bits(3)? {
+        0 => info.bits_per_sample as u32,
+        1 => 8,
+        2 => 12,
+        4 => 16,
+        5 => 20,
+        6 => 24,
+        7 => 32,
+        _ => return Err(invalid("reserved sample size")),
+    };
+    if bits.read_bit()? {
+        return Err(invalid("reserved header bit set"));
+    }
+    read_coded_number(&mut bits)?;
+    let block_size = match block_size_code {
+        0 => return Err(invalid("reserved block size")),
+        1 => 192,
+        2..=5 => 576 << (block_size_code - 2),
+        6 => bits.read_bits(8)? + 1,
+        7 => bits.read_bits(16)? + 1,
+        _ => 256 << (block_size_code - 8),
+    } as usize;
+    match sample_rate_code {
+        12 => _ = bits.read_bits(8)?,
+        13 | 14 => _ = bits.read_bits(16)?,
+        15 => return Err(invalid("reserved sample rate")),
+        _ => {}
+    }
+    let header_len = bits.byte_position();
+    if bits.read_bits(8)? as u8 != crc8(&data[..header_len]) {
+        return Err(FlacError::CrcMismatch { offset });
+    }
+
+    let channel_count = match channels {
+        ChannelAssignment::Independent(count) => count,
+        _ => 2,
+    };
+    if channel_count != info.channels {
+        return Err(invalid("channel count differs from STREAMINFO"));
+    }
+    if bits_per_sample != info.bits_per_sample as u32 {
+        return Err(invalid("sample size differs from STREAMINFO"));
+    }
+    if block_size > info.max_block_size as usize {
+        return Err(invalid("block size above the STREAMINFO maximum"));
+    }
+
+    let mut decoded = vec![vec![0i64; block_size]; channel_count as usize];
+    for (channel, samples) in decoded.iter_mut().enumerate() {
+        // the side channel needs one more bit than the others
+        let side = match channels {
+            ChannelAssignment::LeftSide | ChannelAssignment::MidSide => channel == 1,
+            ChannelAssignment::SideRight => channel == 0,
+            ChannelAssignment::Independent(_) => false,
+        };
+        decode_subframe(&mut bits, bits_per_sample + side as u32, samples)?;
+    }
+    bits.align();
+    let frame_len = bits.byte_position();
+    if bits.read_bits(16)? as u16 != crc16(&data[..frame_len]) {
+        return Err(FlacError::CrcMismatch { offset });
+    }
+
+    decorrelate(channels, &mut decoded);
+    out.reserve(block_size * channel_count as usize);
+    for i in 0..block_size {
+        out.extend(decoded.iter().map(|samples| samples[i] as i32));
+    }
+    Ok(bits.byte_position())
+}
+
+// the frame or sample number, in the UTF-8 style variable length coding extended to 36 bits
+fn read_coded_number(bits: &mut BitReader) -> Result<u64, FlacError> {
+    let first = bits.read_bits(8)?;
+    let continuation_bytes = match (first as u8).leading_ones() {
+        0 => return Ok(first),
+        1 | 8 => return Err(bits.invalid("badly coded frame number")),
+        ones => ones - 1,
+    };
+    let mut number = first & (0x7F >> (continuation_bytes + 1));
+    for _ in 0..continuation_bytes {
+        let byte = bits.read_bits(8)?;
+        if byte & 0xC0 != 0x80 {
+            return Err(bits.invalid("badly coded frame number"));
+        }
+        number = (number << 6) | (byte & 0x3F);
+    }
+    Ok(number)
+}
+
+fn decode_subframe(
+    bits: &mut BitReader,
+    bits_per_sample: u32,
+    samples: &mut [i64],
+) -> Result<(), FlacError> {
+    if bits.read_bit()? {
+        return Err(bits.invalid("subframe padding bit set"));
+    }
+    let subframe_type = bits.read_bits(6)?;
+    let wasted_bits = match bits.read_bit()? {
+        true => bits.read_unary()?.min(u32::MAX as u64 - 1) as u32 + 1,
+        false => 0,
+    };
+    if wasted_bits >= bits_per_sample {
+        return Err(bits.invalid("more wasted bits than bits per sample"));
+    }
+    let bits_per_sample = bits_per_sample - wasted_bits;
+
+    match subframe_type {
+        0 => {
+            let value = bits.read_signed(bits_per_sample)?;
+            samples.fill(value);
+        }
+        1 => {
+            for sample in samples.iter_mut() {
+                *sample = bits.read_signed(bits_per_sample)?;
+            }
+        }
+        8..=12 => {
+            let order = subframe_type as usize - 8;
+            if order > samples.len() {
+                return Err(bits.invalid("predictor order above the block size"));
+            }
+            for sample in samples[..order].iter_mut() {
+                *sample = bits.read_signed(bits_per_sample)?;
+            }
+            read_residual(bits, order, samples)?;
+            restore_fixed(order, samples);
+        }
+        32..=63 => {
+            let order = subframe_type as usize - 31;
+            if order > samples.len() {
+                return Err(bits.invalid("predictor order above the block size"));
+            }
+            for sample in samples[..order].iter_mut() {
+                *sample = bits.read_signed(bits_per_sample)?;
+            }
+            let precision = match bits.read_bits(4)? {
+                15 => return Err(bits.invalid("reserved LPC coefficient precision")),
+                precision => precision as u32 + 1,
+            };
+            let shift = match bits.read_signed(5)? {
+                shift if shift < 0 => return Err(bits.invalid("negative LPC shift")),
+                shift => shift as u32,
+            };
+            let coefficients = (0..order)
+                .map(|_| bits.read_signed(precision))
+                .collect::<Result<Vec<_>, _>>()?;
+            read_residual(bits, order, samples)?;
+            restore_lpc(&coefficients, shift, samples);
+        }
+        _ => return Err(bits.invalid("reserved subframe type")),
+    }
+
+    if wasted_bits > 0 {
+        samples
+            .iter_mut()
+            .for_each(|sample| *sample = sample.wrapping_shl(wasted_bits));
+    }
+    Ok(())
+}
+
+// fills samples[order..] with the Rice coded prediction residual
+fn read_residual(bits: &mut BitReader, order: usize, samples: &mut [i64]) -> Result<(), FlacError> {
+    let (parameter_bits, escape) = match bits.read_bits(2)? {
+        0 => (4, 15),
+        1 => (5, 31),
+        _ => return Err(bits.invalid("reserved residual coding method")),
+    };
+    let partition_order = bits.read_bits(4)? as u32;
+    let partition_len = samples.len() >> partition_order;
+    if partition_len << partition_order != samples.len() || partition_len < order {
+        return Err(bits.invalid("residual partitions don't fit the block"));
+    }
+
+    let mut start = order;
+    for partition in 0..1usize << partition_order {
+        let end = (partition + 1) * partition_len;
+        let parameter = bits.read_bits(parameter_bits)? as u32;
+        if parameter == escape {
+            let raw_bits = bits.read_bits(5)? as u32;
+            for sample in samples[start..end].iter_mut() {
+                *sample = bits.read_signed(raw_bits)?;
+            }
+        } else {
+            for sample in samples[start..end].iter_mut() {
+                *sample = bits.read_rice(parameter)?;
+            }
+        }
+        start = end;
+    }
+    Ok(())
+}
+
+fn restore_fixed(order: usize, samples: &mut [i64]) {
+    let coefficients: &[i64] = match order {
+        0 => return,
+        1 => &[1],
+        2 => &[2, -1],
+        3 => &[3, -3, 1],
+        _ => &[4, -6, 4, -1],
+    };
+    restore_lpc(coefficients, 0, samples);
+}
+
+// coefficients[0] applies to the sample just before the one being predicted
+fn restore_lpc(coefficients: &[i64], shift: u32, samples: &mut [i64]) {
+    let order = coefficients.len();
+    for i in order..samples.len() {
+        let prediction = coefficients
+            .iter()
+            .zip(samples[i - order..i].iter().rev())
+            .fold(0i64, |sum, (coefficient, sample)| {
+                sum.wrapping_add(coefficient.wrapping_mul(*sample))
+            });
+        samples[i] = samples[i].wrapping_add(prediction >> shift);
+    }
+}
+
+fn decorrelate(channels: ChannelAssignment, decoded: &mut [Vec<i64>]) {
+    let [first, second] = decoded else {
+        return;
+    };
+    let pairs = first.iter_mut().zip(second.iter_mut());
+    match channels {
+        ChannelAssignment::Independent(_) => {}
+        ChannelAssignment::LeftSide => {
+            pairs.for_each(|(left, side)| *side = left.wrapping_sub(*side));
+        }
+        ChannelAssignment::SideRight => {
+            pairs.for_each(|(side, right)| *side = side.wrapping_add(*right));
+        }
+        ChannelAssignment::MidSide => pairs.for_each(|(mid, side)| {
+            let sum = mid.wrapping_shl(1) | (*side & 1);
+            (*mid, *side) = (sum.wrapping_add(*side) >> 1, sum.wrapping_sub(*side) >> 1);
+        }),
+    }
+}
+
+#[test]
+fn coded_numbers() {
+    let mut bits = BitReader::new(&[0x24, 0xC3, 0xA9, 0xE2, 0x82, 0xAC, 0xC0], 0);
+    assert_eq!(read_coded_number(&mut bits).unwrap(), 0x24);
+    assert_eq!(read_coded_number(&mut bits).unwrap(), 0xE9);
+    assert_eq!(read_coded_number(&mut bits).unwrap(), 0x20AC);
+    assert!(read_coded_number(&mut bits).is_err());
+
+    // 36 bit sample numbers use all seven bytes
+    let bytes = [0xFE, 0xBF, 0xBF, 0xBF, 0xBF, 0xBF, 0xBF];
+    let mut bits = BitReader::new(&bytes, 0);
+    assert_eq!(read_coded_number(&mut bits).unwrap(), (1 << 36) - 1);
+}
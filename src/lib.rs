@@ -1,9 +1,17 @@
+pub mod aiff;
 pub mod app;
 pub mod audio_analysis;
+mod chunk;
 pub mod circular_buffer;
 pub mod dft;
 mod drain;
 pub mod fft;
+pub mod flac;
 mod iter;
+pub mod resample;
+pub mod stft;
 pub mod wav;
+pub mod window;
+pub mod yin;
+pub mod zoom;
 use app::*;
@@ -0,0 +1,334 @@
+use std::f64::consts::PI;
+
+use crate::window::kaiser;
+
+// rate ratios that need more phases than this interpolate between neighbouring phases
+const MAX_PHASES: u64 = 512;
+
+/// Trade-off between filter length and how cleanly the resampler rejects aliases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    /// short filter for live input, around 60 dB of stopband rejection
+    Fast,
+    /// around 90 dB of rejection with a narrower transition band
+    Balanced,
+    /// long filter for offline work, around 120 dB of rejection
+    Best,
+}
+
+impl Quality {
+    // zero crossings of the sinc on each side, Kaiser beta, and the cutoff relative to
+    // the lower of the two Nyquist frequencies
+    fn design(self) -> (usize, f64, f64) {
+        match self {
+            Quality::Fast => (8, 6.0, 0.85),
+            Quality::Balanced => (16, 9.0, 0.91),
+            Quality::Best => (32, 12.0, 0.94),
+        }
+    }
+}
+
+/// Band-limited sample rate converter using a polyphase windowed-sinc filter.
+///
+/// Output sample `n` lines up exactly with input time `n * input_rate / output_rate`,
+/// so converted signals keep their timing. Input can be fed in blocks of any size.
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    // the rate ratio reduced to output_rate / input_rate = up / down
+    up: u64,
+    down: u64,
+    phases: u64,
+    taps: usize,
+    // (phases + 1) rows of `taps` coefficients, row p for a fractional delay of p / phases
+    filter: Box<[f32]>,
+    history: Vec<f32>,
+    // history index of the input sample at or just before the next output, plus the
+    // fraction past it in 1/up of a sample
+    index: usize,
+    fraction: u64,
+    input_count: u64,
+    output_count: u64,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32, quality: Quality) -> Self {
+        assert!(
+            input_rate > 0 && output_rate > 0,
+            "sample rates must be positive"
+        );
+        let divisor = gcd(input_rate as u64, output_rate as u64);
+        let (up, down) = (output_rate as u64 / divisor, input_rate as u64 / divisor);
+        let phases = up.min(MAX_PHASES);
+
+        // when downsampling the filter has to cut below the output Nyquist frequency,
+        // which stretches it over more input samples
+        let (zero_crossings, beta, rolloff) = quality.design();
+        let scale = (up as f64 / down as f64).min(1.0);
+        let cutoff = rolloff * scale;
+        let half_width = (zero_crossings as f64 / scale).ceil() as usize;
+        let taps = 2 * half_width;
+
+        let mut filter = vec![0.0; (phases as usize + 1) * taps];
+        for (phase, row) in filter.chunks_exact_mut(taps).enumerate() {
+            let delay = phase as f64 / phases as f64;
+            let weights = (0..taps)
+                .map(|tap| {
+                    // distance from the output time to the input sample this tap reads
+                    let x = delay + half_width as f64 - 1.0 - tap as f64;
+                    cutoff * sinc(cutoff * x) * kaiser(x / half_width as f64, beta)
+                })
+                .collect::<Vec<_>>();
+            // exact unity gain at DC, whatever the phase
+            let sum = weights.iter().sum::<f64>();
+            row.iter_mut()
+                .zip(weights)
+                .for_each(|(coefficient, weight)| *coefficient = (weight / sum) as f32);
+        }
+
+        let mut resampler = Self {
+            input_rate,
+            output_rate,
+            up,
+            down,
+            phases,
+            taps,
+            filter: filter.into_boxed_slice(),
+            history: vec![],
+            index: 0,
+            fraction: 0,
+            input_count: 0,
+            output_count: 0,
+        };
+        resampler.reset();
+        resampler
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// How many input samples `process` holds back before the output catches up with them.
+    pub fn latency(&self) -> usize {
+        self.taps / 2
+    }
+
+    /// Forgets all buffered input, ready to convert an unrelated signal.
+    pub fn reset(&mut self) {
+        // silence before the first sample, so the first output has a full filter
+        self.history.clear();
+        self.history.resize(self.taps / 2 - 1, 0.0);
+        self.index = self.taps / 2 - 1;
+        self.fraction = 0;
+        self.input_count = 0;
+        self.output_count = 0;
+    }
+
+    /// Converts the next block of input, returning every output sample it completes.
+    pub fn process(&mut self, input: &[f32]) -> Box<[f32]> {
+        let mut output = vec![];
+        self.process_into(input, &mut output);
+        output.into_boxed_slice()
+    }
+
+    /// Like `process`, replacing the contents of `output` so that its capacity is reused.
+    /// Once `output` has grown to fit a block, converting that block doesn't allocate.
+    pub fn process_into(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        output.clear();
+        self.history.extend_from_slice(input);
+        self.input_count += input.len() as u64;
+        self.drain_output(u64::MAX, output);
+    }
+
+    /// Converts whatever input is still buffered as if the signal ended in silence, then resets.
+    ///
+    /// The total output over `process` and `flush` is `ceil(input_len * output_rate / input_rate)`.
+    pub fn flush(&mut self) -> Box<[f32]> {
+        let expected = (self.input_count * self.up).div_ceil(self.down);
+        self.history.resize(self.history.len() + self.taps / 2, 0.0);
+        let mut output = vec![];
+        self.drain_output(expected, &mut output);
+        self.reset();
+        output.into_boxed_slice()
+    }
+
+    fn drain_output(&mut self, limit: u64, output: &mut Vec<f32>) {
+        let half_width = self.taps / 2;
+        while self.index + half_width < self.history.len() && self.output_count < limit {
+            let window = &self.history[self.index + 1 - half_width..=self.index + half_width];
+            let position = self.fraction * self.phases;
+            let phase = (position / self.up) as usize;
+            let row = &self.filter[phase * self.taps..(phase + 1) * self.taps];
+            let mut sample = dot(window, row);
+            // between two of the tabulated phases
+            let remainder = position % self.up;
+            if remainder != 0 {
+                let next = &self.filter[(phase + 1) * self.taps..(phase + 2) * self.taps];
+                let weight = remainder as f32 / self.up as f32;
+                sample += weight * (dot(window, next) - sample);
+            }
+            output.push(sample);
+            self.output_count += 1;
+
+            let step = self.fraction + self.down;
+            self.index += (step / self.up) as usize;
+            self.fraction = step % self.up;
+        }
+
+        // keep only what the next output still reads
+        let consumed = (self.index + 1).saturating_sub(half_width);
+        let consumed = consumed.min(self.history.len());
+        self.history.drain(..consumed);
+        self.index -= consumed;
+    }
+}
+
+/// Converts a whole signal at once; see `Resampler` for how the output lines up.
+pub fn resample(
+    samples: &[f32],
+    input_rate: u32,
+    output_rate: u32,
+    quality: Quality,
+) -> Box<[f32]> {
+    let mut resampler = Resampler::new(input_rate, output_rate, quality);
+    let mut output = resampler.process(samples).into_vec();
+    output.extend_from_slice(&resampler.flush());
+    output.into_boxed_slice()
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+fn sine(freq: f64, sample_rate: u32, len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| (2.0 * PI * freq * i as f64 / sample_rate as f64).sin() as f32)
+        .collect()
+}
+
+// largest difference from the ideal sine, away from the edges where the filter sees silence
+#[cfg(test)]
+fn sine_error(output: &[f32], freq: f64, sample_rate: u32) -> f32 {
+    let ideal = sine(freq, sample_rate, output.len());
+    let edge = output.len() / 8;
+    output[edge..output.len() - edge]
+        .iter()
+        .zip(&ideal[edge..])
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn converts_sines() {
+    for (input_rate, output_rate) in [
+        (44100, 48000),
+        (22050, 48000),
+        (192000, 48000),
+        (48000, 44100),
+        // no small ratio, so phases are interpolated
+        (44100, 47999),
+    ] {
+        for (quality, tolerance) in [
+            (Quality::Fast, 3e-3),
+            (Quality::Balanced, 2e-4),
+            (Quality::Best, 5e-5),
+        ] {
+            let input = sine(1000.0, input_rate, input_rate as usize / 4);
+            let output = resample(&input, input_rate, output_rate, quality);
+            assert_eq!(
+                output.len() as u64,
+                (input.len() as u64 * output_rate as u64).div_ceil(input_rate as u64)
+            );
+            let error = sine_error(&output, 1000.0, output_rate);
+            assert!(
+                error < tolerance,
+                "{} -> {} Hz {:?}: error {}",
+                input_rate,
+                output_rate,
+                quality,
+                error
+            );
+        }
+    }
+}
+
+#[test]
+fn rejects_aliases() {
+    // 30 kHz would fold back to 18 kHz at 48 kHz
+    let input = sine(30000.0, 192000, 48000);
+    for (quality, limit) in [
+        (Quality::Fast, 2e-3),
+        (Quality::Balanced, 1e-4),
+        (Quality::Best, 1e-5),
+    ] {
+        let output = resample(&input, 192000, 48000, quality);
+        let edge = output.len() / 8;
+        let peak = output[edge..output.len() - edge]
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak < limit, "{:?}: alias at {}", quality, peak);
+    }
+}
+
+#[test]
+fn streams_like_one_shot() {
+    let input = sine(440.0, 44100, 10000);
+    let expected = resample(&input, 44100, 48000, Quality::Balanced);
+
+    let mut resampler = Resampler::new(44100, 48000, Quality::Balanced);
+    let mut streamed = vec![];
+    let mut rest = &input[..];
+    for block_len in [1, 7, 512, 1000, 3].iter().cycle() {
+        if rest.is_empty() {
+            break;
+        }
+        let (block, tail) = rest.split_at((*block_len).min(rest.len()));
+        streamed.extend_from_slice(&resampler.process(block));
+        rest = tail;
+    }
+    streamed.extend_from_slice(&resampler.flush());
+    assert_eq!(&streamed[..], &expected[..]);
+
+    // the same through one reused output buffer
+    let mut streamed = vec![];
+    let mut output = vec![];
+    for block in input.chunks(256) {
+        resampler.process_into(block, &mut output);
+        streamed.extend_from_slice(&output);
+    }
+    streamed.extend_from_slice(&resampler.flush());
+    assert_eq!(&streamed[..], &expected[..]);
+}
+
+#[test]
+fn analyzes_after_conversion() {
+    use crate::audio_analysis::{AudioAnalyzer, Note, WindowType};
+
+    for input_rate in [22050, 44100, 96000, 192000] {
+        let input = sine(440.0, input_rate, input_rate as usize);
+        let output = resample(&input, input_rate, 48000, Quality::Fast);
+        let mut analyzer = AudioAnalyzer::new(48000, 1024 * 32, 0, 3, 440, WindowType::Hann);
+        analyzer.add_samples(&output);
+        assert_eq!(Note::from_frequency(analyzer.strongest_freq()), Note::A);
+    }
+}
@@ -0,0 +1,267 @@
+use num_complex::Complex;
+
+use crate::{
+    fft::FftPlan,
+    window::{Symmetry, Window, WindowType},
+};
+
+/// One windowed slice of the signal in polar form, from DC up to Nyquist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StftFrame {
+    /// unscaled, so a full scale sine at a bin's exact frequency reads about a quarter of the
+    /// frame length with a Hann window; `Window::sine_amplitude` undoes that
+    pub magnitudes: Box<[f32]>,
+    /// in radians, between -π and π
+    pub phases: Box<[f32]>,
+}
+
+impl StftFrame {
+    pub fn bins(&self) -> usize {
+        self.magnitudes.len()
+    }
+
+    /// The bin with the most energy, DC included.
+    pub fn peak_bin(&self) -> usize {
+        self.magnitudes
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(0, |(bin, _)| bin)
+    }
+}
+
+/// Short-time Fourier transform: a window slid along the signal `hop` samples at a time,
+/// with each windowed slice transformed on its own.
+///
+/// Frame `k` is centred on sample `k * hop`, with silence before and after the signal, so
+/// there are `len / hop + 1` frames and every sample lies under at least one. `inverse`
+/// overlap-adds the frames back together, dividing out the summed squared window, and gets
+/// the signal back exactly for any hop up to half the frame length.
+pub struct Stft {
+    sample_rate: u32,
+    hop: usize,
+    window: Window,
+    plan: FftPlan,
+    buffer: Box<[f32]>,
+    spectrum: Box<[Complex<f32>]>,
+    scratch: Box<[Complex<f32>]>,
+}
+
+impl Stft {
+    /// # Panics
+    /// If `frame_len` is zero, or `hop` isn't between 1 and `frame_len`.
+    pub fn new(sample_rate: u32, frame_len: usize, hop: usize, window_type: WindowType) -> Self {
+        assert!(frame_len > 0, "STFT frames can't be empty");
+        assert!(
+            hop > 0 && hop <= frame_len,
+            "the STFT hop has to be between 1 and the frame length"
+        );
+        let window = Window::new(window_type, frame_len, Symmetry::Periodic);
+        let plan = FftPlan::new(frame_len).unwrap();
+        Self {
+            sample_rate,
+            hop,
+            window,
+            buffer: vec![0.0; frame_len].into_boxed_slice(),
+            spectrum: vec![Complex::new(0.0, 0.0); frame_len / 2 + 1].into_boxed_slice(),
+            scratch: vec![Complex::new(0.0, 0.0); plan.scratch_len()].into_boxed_slice(),
+            plan,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn frame_len(&self) -> usize {
+        self.window.size()
+    }
+
+    /// The analysis window, periodic so that frames tile cleanly.
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Bins in each frame, from DC up to Nyquist.
+    pub fn bins(&self) -> usize {
+        self.spectrum.len()
+    }
+
+    /// Centre frequency of `bin` in Hz.
+    pub fn bin_frequency(&self, bin: usize) -> f32 {
+        (bin as f64 * self.sample_rate as f64 / self.frame_len() as f64) as f32
+    }
+
+    /// Time in seconds that frame `index` is centred on.
+    pub fn frame_time(&self, index: usize) -> f32 {
+        (index as f64 * self.hop as f64 / self.sample_rate as f64) as f32
+    }
+
+    /// Frames covering `len` samples.
+    pub fn frame_count(&self, len: usize) -> usize {
+        match len {
+            0 => 0,
+            len => len / self.hop + 1,
+        }
+    }
+
+    /// Slices the whole signal into frames.
+    pub fn forward(&mut self, samples: &[f32]) -> Box<[StftFrame]> {
+        (0..self.frame_count(samples.len()))
+            .map(|index| {
+                let start = self.frame_start(index);
+                for (i, (value, window)) in self
+                    .buffer
+                    .iter_mut()
+                    .zip(self.window.samples())
+                    .enumerate()
+                {
+                    let sample = usize::try_from(start + i as isize)
+                        .ok()
+                        .and_then(|position| samples.get(position));
+                    *value = sample.map_or(0.0, |sample| sample * window);
+                }
+                self.plan.real_forward_with_scratch(
+                    &self.buffer,
+                    &mut self.spectrum,
+                    &mut self.scratch,
+                );
+                StftFrame {
+                    magnitudes: self.spectrum.iter().map(|bin| bin.norm()).collect(),
+                    phases: self.spectrum.iter().map(|bin| bin.arg()).collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Turns frames back into `len` samples by weighted overlap-add, which also works on
+    /// frames that were edited after `forward`.
+    ///
+    /// # Panics
+    /// If a frame doesn't have `bins()` bins.
+    pub fn inverse(&mut self, frames: &[StftFrame], len: usize) -> Box<[f32]> {
+        let mut output = vec![0.0; len];
+        let mut weights = vec![0.0; len];
+        for (index, frame) in frames.iter().enumerate() {
+            assert!(
+                frame.bins() == self.bins() && frame.phases.len() == self.bins(),
+                "STFT frame has the wrong number of bins"
+            );
+            self.spectrum
+                .iter_mut()
+                .zip(frame.magnitudes.iter().zip(frame.phases.iter()))
+                .for_each(|(bin, (magnitude, phase))| {
+                    *bin = Complex::from_polar(*magnitude, *phase)
+                });
+            self.plan.real_inverse_with_scratch(
+                &mut self.spectrum,
+                &mut self.buffer,
+                &mut self.scratch,
+                true,
+            );
+
+            let start = self.frame_start(index);
+            for (i, (value, window)) in self.buffer.iter().zip(self.window.samples()).enumerate() {
+                let position = usize::try_from(start + i as isize).ok();
+                if let Some(position) = position.filter(|position| *position < len) {
+                    output[position] += value * window;
+                    weights[position] += window * window;
+                }
+            }
+        }
+        // samples no window reaches stay silent
+        output
+            .iter_mut()
+            .zip(weights)
+            .for_each(|(sample, weight)| match weight > 1e-6 {
+                true => *sample /= weight,
+                false => *sample = 0.0,
+            });
+        output.into_boxed_slice()
+    }
+
+    // frames are centred on multiples of the hop, so this goes negative near the start
+    fn frame_start(&self, index: usize) -> isize {
+        (index * self.hop) as isize - (self.frame_len() / 2) as isize
+    }
+}
+
+/// Spectrogram of a whole signal; see `Stft` for how frames line up.
+pub fn stft(
+    samples: &[f32],
+    sample_rate: u32,
+    frame_len: usize,
+    hop: usize,
+    window_type: WindowType,
+) -> Box<[StftFrame]> {
+    Stft::new(sample_rate, frame_len, hop, window_type).forward(samples)
+}
+
+#[test]
+fn reconstructs() {
+    let signal = (0..5000)
+        .map(|i| ((i * 7919) % 31) as f32 / 31.0 - 0.5 + (i as f32 * 0.01).sin())
+        .collect::<Box<[f32]>>();
+    for (frame_len, hop, window) in [
+        (512, 128, WindowType::Hann),
+        (512, 256, WindowType::Hann),
+        (1000, 250, WindowType::Hamming),
+        (257, 100, WindowType::Hann),
+        (600, 150, WindowType::BlackmanHarris),
+        (512, 64, WindowType::Kaiser(9.0)),
+    ] {
+        let mut stft = Stft::new(48000, frame_len, hop, window);
+        let frames = stft.forward(&signal);
+        assert_eq!(frames.len(), signal.len() / hop + 1);
+        assert!(frames.iter().all(|frame| frame.bins() == frame_len / 2 + 1));
+        let restored = stft.inverse(&frames, signal.len());
+        for (a, b) in restored.iter().zip(signal.iter()) {
+            assert!(
+                (a - b).abs() < 1e-4,
+                "{} / {}: {} vs {}",
+                frame_len,
+                hop,
+                a,
+                b
+            );
+        }
+    }
+    assert!(Stft::new(48000, 64, 16, WindowType::Hann)
+        .forward(&[])
+        .is_empty());
+}
+
+#[test]
+fn tracks_pitch() {
+    use crate::{audio_analysis::Note, wav::WavFile};
+    use std::io::Cursor;
+
+    // a second of A followed by a second of B
+    let mut signal = vec![];
+    for file in [
+        &include_bytes!("../A.wav")[..],
+        &include_bytes!("../B.wav")[..],
+    ] {
+        let wav = WavFile::from_bytes(&mut Cursor::new(file)).unwrap();
+        signal.extend_from_slice(&wav.get_samples().unwrap()[..48000]);
+    }
+
+    let mut stft = Stft::new(48000, 8192, 2048, WindowType::Hann);
+    let frames = stft.forward(&signal);
+    for (index, frame) in frames.iter().enumerate() {
+        let time = stft.frame_time(index);
+        let note = Note::from_frequency(stft.bin_frequency(frame.peak_bin()));
+        // frames straddling the change hear both
+        if time < 0.9 {
+            assert_eq!(note, Note::A, "at {}s", time);
+        } else if time > 1.1 && time < 1.9 {
+            assert_eq!(note, Note::B, "at {}s", time);
+        }
+    }
+    assert_eq!(stft.frame_time(24), 1.024);
+    assert_eq!(stft.bin_frequency(4096), 24000.0);
+}
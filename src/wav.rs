@@ -1,25 +1,92 @@
 use core::fmt;
 use std::{
     ffi::CStr,
-    io::{Cursor, Read, Seek, SeekFrom},
+    io::{Cursor, Read, Seek, SeekFrom, Take, Write},
     num::{NonZero, NonZeroU32},
     str::{self, Bytes},
 };
 
-use anyhow::{anyhow, Ok};
-use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 use imgui_glow_renderer::glow::COPY_READ_BUFFER;
 use sdl2::libc::SOCKET;
 
 const RIFF_HEADER: [u8; 4] = [0x52, 0x49, 0x46, 0x46]; //RIFF
 const RIFX_HEADER: [u8; 4] = [0x52, 0x49, 0x46, 0x58]; //RIFX
+const RF64_HEADER: [u8; 4] = [0x52, 0x46, 0x36, 0x34]; //RF64
+const BW64_HEADER: [u8; 4] = [0x42, 0x57, 0x36, 0x34]; //BW64
+const DS64_HEADER: [u8; 4] = [0x64, 0x73, 0x36, 0x34]; //ds64
 const WAVE_HEADER: [u8; 4] = [0x57, 0x41, 0x56, 0x45]; //WAVE
 const FMT_HEADER: [u8; 4] = [0x66, 0x6D, 0x74, 0x20]; //FMT
 const FACT_HEADER: [u8; 4] = [0x66, 0x61, 0x63, 0x74]; //fact
 const PEAK_HEADER: [u8; 4] = [0x50, 0x45, 0x41, 0x4B]; //PEAK
 const DATA_HEADER: [u8; 4] = [0x64, 0x61, 0x74, 0x61]; //data
+const LIST_HEADER: [u8; 4] = [0x4C, 0x49, 0x53, 0x54]; //LIST
+const INFO_HEADER: [u8; 4] = [0x49, 0x4E, 0x46, 0x4F]; //INFO
+const BEXT_HEADER: [u8; 4] = [0x62, 0x65, 0x78, 0x74]; //bext
+const SMPL_HEADER: [u8; 4] = [0x73, 0x6D, 0x70, 0x6C]; //smpl
+const CUE_HEADER: [u8; 4] = [0x63, 0x75, 0x65, 0x20]; //cue
 
-#[derive(Debug)]
+mod channels;
+mod decode;
+mod error;
+mod extensible;
+mod metadata;
+mod reader;
+mod sampler;
+mod writer;
+
+pub use channels::{channel_iter, deinterleave, downmix, ChannelSelection};
+pub use error::WavError;
+pub use extensible::{
+    ChannelMask, Guid, Speaker, KSDATAFORMAT_SUBTYPE_ALAW, KSDATAFORMAT_SUBTYPE_IEEE_FLOAT,
+    KSDATAFORMAT_SUBTYPE_MULAW, KSDATAFORMAT_SUBTYPE_PCM,
+};
+pub use metadata::{
+    Bext, InfoList, Metadata, INFO_ARTIST, INFO_COMMENT, INFO_COPYRIGHT, INFO_CREATION_DATE,
+    INFO_ENGINEER, INFO_GENRE, INFO_KEYWORDS, INFO_NAME, INFO_PRODUCT, INFO_SOFTWARE, INFO_SUBJECT,
+};
+pub use reader::{Blocks, WavReader};
+pub use sampler::{CuePoint, SampleLoop, Sampler};
+pub use writer::{WavSpec, WavWriter};
+
+use crate::chunk::{bytes_remaining, read_chunk_bounds};
+use extensible::FmtExtensible;
+
+/// The outer RIFF form of a file, which decides byte order and size field width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Riff,
+    /// big endian RIFF
+    Rifx,
+    /// 64 bit sizes stored in a ds64 chunk
+    Rf64,
+    /// ITU-R BS.2088 name for RF64
+    Bw64,
+}
+impl Container {
+    fn from_header(header: [u8; 4]) -> Option<Self> {
+        match header {
+            RIFF_HEADER => Some(Self::Riff),
+            RIFX_HEADER => Some(Self::Rifx),
+            RF64_HEADER => Some(Self::Rf64),
+            BW64_HEADER => Some(Self::Bw64),
+            _ => None,
+        }
+    }
+    fn header(self) -> [u8; 4] {
+        match self {
+            Self::Riff => RIFF_HEADER,
+            Self::Rifx => RIFX_HEADER,
+            Self::Rf64 => RF64_HEADER,
+            Self::Bw64 => BW64_HEADER,
+        }
+    }
+    fn has_ds64(self) -> bool {
+        matches!(self, Self::Rf64 | Self::Bw64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u16)]
 pub enum WavFormat {
     PCM = 0x0001,
@@ -30,14 +97,14 @@ pub enum WavFormat {
     INVALID = 0xFFFF,
 }
 impl WavFormat {
-    fn from_u16(value: u16) -> Result<Self, anyhow::Error> {
+    fn from_u16(value: u16) -> Option<Self> {
         match value {
-            1 => Ok(Self::PCM),
-            3 => Ok(Self::Float),
-            6 => Ok(Self::ALaw),
-            7 => Ok(Self::MuLaw),
-            65534 => Ok(Self::Extensible),
-            _ => Err(anyhow!("Unknown value for WavFormat")),
+            1 => Some(Self::PCM),
+            3 => Some(Self::Float),
+            6 => Some(Self::ALaw),
+            7 => Some(Self::MuLaw),
+            65534 => Some(Self::Extensible),
+            _ => None,
         }
     }
 }
@@ -47,6 +114,11 @@ enum RiffChunk {
     Fact(FactChunk),
     Peak(PeakChunk),
     Data(DataChunk),
+    Info(InfoList),
+    Bext(Bext),
+    Sampler(Sampler),
+    Cue(Vec<CuePoint>),
+    Raw(RawChunk),
 }
 
 impl RiffChunk {
@@ -103,7 +175,6 @@ impl RiffChunk {
 #[derive(Debug)]
 struct FmtChunk {
     fmt_str: [u8; 4],
-    chunk_size: u32,
     format: WavFormat,
     channels: u16,
     sample_rate: u32,
@@ -111,34 +182,49 @@ struct FmtChunk {
     block_align: u16,
     bits_per_sample: u16,
     extra_data_size: Option<u16>,
-    extra_data: Option<Box<[u8]>>,
+    extensible: Option<FmtExtensible>,
+    extra_data: Option<Box<[u8]>>, // anything after the extensible fields
 }
 impl FmtChunk {
-    fn read<T: Read + Seek>(data: &mut T, header: [u8; 4]) -> Result<Self, anyhow::Error> {
-        let chunk_size = data.read_u32::<LittleEndian>()?;
-        let format = WavFormat::from_u16(data.read_u16::<LittleEndian>()?)?;
-        let channels = data.read_u16::<LittleEndian>()?;
-        let sample_rate = data.read_u32::<LittleEndian>()?;
-        let byte_rate = data.read_u32::<LittleEndian>()?;
-        let block_align = data.read_u16::<LittleEndian>()?;
-        let bits_per_sample = data.read_u16::<LittleEndian>()?;
-        let (extra_data_size, extra_data) = if chunk_size != 16 {
+    fn read<E: ByteOrder, T: Read>(data: &mut T, header: &ChunkHeader) -> Result<Self, WavError> {
+        let tag = data.read_u16::<E>()?;
+        let format = WavFormat::from_u16(tag).ok_or(WavError::UnknownFormat {
+            offset: header.offset(),
+            tag,
+        })?;
+        let channels = data.read_u16::<E>()?;
+        let sample_rate = data.read_u32::<E>()?;
+        let byte_rate = data.read_u32::<E>()?;
+        let block_align = data.read_u16::<E>()?;
+        let bits_per_sample = data.read_u16::<E>()?;
+        let (extra_data_size, extra_data) = if header.size != 16 {
             //not standard, we need to read extra data
-            let extra_data_size = data.read_u16::<LittleEndian>()?;
-            let bytes_remaining = bytes_remaining(data)?;
-            if bytes_remaining < extra_data_size as u64 {
-                return Err(anyhow!("Unexpected EOF reading extra fmt data"));
-            }
-            let extra_data = (0..extra_data_size)
-                .map(|_| data.read_u8().unwrap())
-                .collect::<Box<_>>();
-            (Some(extra_data_size), Some(extra_data))
+            let extra_data_size = data.read_u16::<E>()?;
+            let mut extra_data = vec![0; extra_data_size as usize];
+            data.read_exact(&mut extra_data)?;
+            (Some(extra_data_size), Some(extra_data.into_boxed_slice()))
         } else {
             (None, None)
         };
+        let (extensible, extra_data) = match (format, extra_data) {
+            (WavFormat::Extensible, Some(extra_data))
+                if extra_data.len() >= extensible::EXTENSIBLE_SIZE as usize =>
+            {
+                let extensible = FmtExtensible::parse::<E>(&extra_data);
+                let rest = extra_data[extensible::EXTENSIBLE_SIZE as usize..].into();
+                (Some(extensible), Some(rest))
+            }
+            (WavFormat::Extensible, _) => {
+                return Err(WavError::InvalidChunk {
+                    chunk: header.id,
+                    offset: header.offset(),
+                    reason: "extensible format without its 22 byte extension",
+                });
+            }
+            (_, extra_data) => (None, extra_data),
+        };
         Ok(Self {
-            fmt_str: header,
-            chunk_size,
+            fmt_str: header.id,
             format,
             channels,
             sample_rate,
@@ -146,68 +232,143 @@ impl FmtChunk {
             block_align,
             bits_per_sample,
             extra_data_size,
+            extensible,
             extra_data,
         })
     }
+    /// The format of the stored samples, looking through WAVE_FORMAT_EXTENSIBLE.
+    fn sample_format(&self) -> Result<WavFormat, WavError> {
+        match &self.extensible {
+            Some(extensible) => extensible.sample_format(),
+            None => Ok(self.format),
+        }
+    }
+    /// Storage size of a single sample.
+    fn container_bytes(&self) -> Result<usize, WavError> {
+        if self.channels == 0
+            || self.block_align == 0
+            || !self.block_align.is_multiple_of(self.channels)
+        {
+            return Err(WavError::InvalidBlockAlign {
+                block_align: self.block_align,
+                channels: self.channels,
+            });
+        }
+        Ok((self.block_align / self.channels) as usize)
+    }
+    /// Significant bits per sample, which extensible files may set below the container size.
+    fn valid_bits_per_sample(&self) -> u16 {
+        match &self.extensible {
+            Some(extensible) if extensible.valid_bits_per_sample != 0 => {
+                extensible.valid_bits_per_sample
+            }
+            _ => self.bits_per_sample,
+        }
+    }
+    fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        let extension_size = self.extra_data_size.map_or(0, |_| 2)
+            + self
+                .extensible
+                .map_or(0, |_| extensible::EXTENSIBLE_SIZE as u32)
+            + self
+                .extra_data
+                .as_ref()
+                .map_or(0, |extra| extra.len() as u32);
+        out.write_all(&self.fmt_str)?;
+        out.write_u32::<E>(16 + extension_size)?;
+        out.write_u16::<E>(self.format as u16)?;
+        out.write_u16::<E>(self.channels)?;
+        out.write_u32::<E>(self.sample_rate)?;
+        out.write_u32::<E>(self.byte_rate)?;
+        out.write_u16::<E>(self.block_align)?;
+        out.write_u16::<E>(self.bits_per_sample)?;
+        if let Some(extra_data_size) = self.extra_data_size {
+            out.write_u16::<E>(extra_data_size)?;
+        }
+        if let Some(extensible) = &self.extensible {
+            extensible.write::<E, _>(out)?;
+        }
+        if let Some(extra_data) = &self.extra_data {
+            out.write_all(extra_data)?;
+        }
+        Ok(())
+    }
 }
 #[derive(Debug)]
 struct FactChunk {
     fact_str: [u8; 4],
-    chunk_size: u32,
     data: u32, // typically number of samples
 }
 
 impl FactChunk {
-    fn read<T: Read + Seek>(data: &mut T, header: [u8; 4]) -> Result<Self, anyhow::Error> {
-        let chunk_size = data.read_u32::<LittleEndian>()?;
-        let data = data.read_u32::<LittleEndian>()?;
+    fn read<E: ByteOrder, T: Read>(data: &mut T, header: &ChunkHeader) -> Result<Self, WavError> {
+        let data = data.read_u32::<E>()?;
         Ok(Self {
-            fact_str: header,
-            chunk_size,
+            fact_str: header.id,
             data,
         })
     }
+    fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        out.write_all(&self.fact_str)?;
+        out.write_u32::<E>(4)?;
+        out.write_u32::<E>(self.data)?;
+        Ok(())
+    }
 }
 #[derive(Debug)]
 struct PeakChunk {
     peak_str: [u8; 4],
-    chunk_size: u32,
     version: u32,
     time_stamp: u32,
-    peak: PositionPeak,
+    peaks: Box<[PositionPeak]>, // one per channel
 }
 impl PeakChunk {
-    fn read<T: Read + Seek>(data: &mut T, header: [u8; 4]) -> Result<Self, anyhow::Error> {
-        let chunk_size = data.read_u32::<LittleEndian>()?;
-        let version = data.read_u32::<LittleEndian>()?;
-        let time_stamp = data.read_u32::<LittleEndian>()?;
-        let peak = PositionPeak::read(data)?;
+    fn read<E: ByteOrder, T: Read>(data: &mut T, header: &ChunkHeader) -> Result<Self, WavError> {
+        let version = data.read_u32::<E>()?;
+        let time_stamp = data.read_u32::<E>()?;
+        let peaks = (0..header.size.saturating_sub(8) / 8)
+            .map(|_| PositionPeak::read::<E, _>(data))
+            .collect::<Result<Box<_>, _>>()?;
         Ok(Self {
-            peak_str: header,
-            chunk_size,
+            peak_str: header.id,
             version,
             time_stamp,
-            peak: peak,
+            peaks,
         })
     }
+    fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        out.write_all(&self.peak_str)?;
+        out.write_u32::<E>(8 + 8 * self.peaks.len() as u32)?;
+        out.write_u32::<E>(self.version)?;
+        out.write_u32::<E>(self.time_stamp)?;
+        for peak in self.peaks.iter() {
+            peak.write::<E, _>(out)?;
+        }
+        Ok(())
+    }
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, Default)]
 struct PositionPeak {
-    value: u32,
+    value: f32,
     position: u32,
 }
 impl PositionPeak {
-    fn read<T: Read + Seek>(data: &mut T) -> Result<Self, anyhow::Error> {
-        let value = data.read_u32::<LittleEndian>()?;
-        let position = data.read_u32::<LittleEndian>()?;
+    fn read<E: ByteOrder, T: Read>(data: &mut T) -> Result<Self, WavError> {
+        let value = data.read_f32::<E>()?;
+        let position = data.read_u32::<E>()?;
 
         Ok(Self { value, position })
     }
+    fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        out.write_f32::<E>(self.value)?;
+        out.write_u32::<E>(self.position)?;
+        Ok(())
+    }
 }
 
 struct DataChunk {
     data_str: [u8; 4],
-    chunk_size: u32,
+    chunk_size: u64,
     data: Box<[u8]>,
 }
 impl fmt::Debug for DataChunk {
@@ -221,124 +382,538 @@ impl fmt::Debug for DataChunk {
 }
 
 impl DataChunk {
-    fn read<T: Read + Seek>(data: &mut T, header: [u8; 4]) -> Result<Self, anyhow::Error> {
-        let chunk_size = data.read_u32::<LittleEndian>()?;
-        let mut vec = vec![0; chunk_size as usize];
+    fn read<T: Read>(data: &mut T, header: &ChunkHeader) -> Result<Self, WavError> {
+        let mut vec = vec![0; header.size as usize];
+        data.read_exact(&mut vec[..])?;
+        Ok(Self {
+            data_str: header.id,
+            chunk_size: header.size,
+            data: vec.into_boxed_slice(),
+        })
+    }
+    fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        write_padded_chunk::<E, _>(out, self.data_str, &self.data)
+    }
+    // the real size lives in the ds64 chunk
+    fn write_rf64<W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        out.write_all(&self.data_str)?;
+        out.write_u32::<LittleEndian>(u32::MAX)?;
+        out.write_all(&self.data)?;
+        if self.data.len() % 2 == 1 {
+            out.write_u8(0)?;
+        }
+        Ok(())
+    }
+}
+
+/// A chunk this module does not interpret, kept verbatim so it survives a rewrite.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RawChunk {
+    pub id: [u8; 4],
+    pub data: Box<[u8]>,
+}
+impl fmt::Debug for RawChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RawChunk")
+            .field("id", &String::from_utf8_lossy(&self.id))
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+impl RawChunk {
+    fn read<T: Read>(data: &mut T, header: &ChunkHeader) -> Result<Self, WavError> {
+        let mut vec = vec![0; header.size as usize];
         data.read_exact(&mut vec[..])?;
         Ok(Self {
-            data_str: header,
-            chunk_size,
+            id: header.id,
             data: vec.into_boxed_slice(),
         })
     }
+    fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        write_padded_chunk::<E, _>(out, self.id, &self.data)
+    }
+}
+
+fn write_padded_chunk<E: ByteOrder, W: Write>(
+    out: &mut W,
+    id: [u8; 4],
+    data: &[u8],
+) -> Result<(), WavError> {
+    let chunk_size = u32::try_from(data.len()).map_err(|_| WavError::TooLarge {
+        size: data.len() as u64,
+    })?;
+    out.write_all(&id)?;
+    out.write_u32::<E>(chunk_size)?;
+    out.write_all(data)?;
+    // chunks are word aligned
+    if data.len() % 2 == 1 {
+        out.write_u8(0)?;
+    }
+    Ok(())
+}
+
+/// Real sizes of an RF64/BW64 file whose 32 bit size fields are set to `u32::MAX`.
+#[derive(Debug, Default)]
+struct Ds64Chunk {
+    riff_size: u64,
+    data_size: u64,
+    sample_count: u64,
+    table: Vec<([u8; 4], u64)>,
+}
+impl Ds64Chunk {
+    fn read<T: Read>(data: &mut T, header: &ChunkHeader) -> Result<Self, WavError> {
+        let riff_size = data.read_u64::<LittleEndian>()?;
+        let data_size = data.read_u64::<LittleEndian>()?;
+        let sample_count = data.read_u64::<LittleEndian>()?;
+        let table_length = data.read_u32::<LittleEndian>()?;
+        if (table_length as u64) * 12 > header.size.saturating_sub(28) {
+            return Err(WavError::InvalidChunk {
+                chunk: header.id,
+                offset: header.offset(),
+                reason: "table runs past the end of the chunk",
+            });
+        }
+        let table = (0..table_length)
+            .map(|_| {
+                let mut id = [0u8; 4];
+                data.read_exact(&mut id)?;
+                Ok((id, data.read_u64::<LittleEndian>()?))
+            })
+            .collect::<Result<Vec<_>, WavError>>()?;
+        Ok(Self {
+            riff_size,
+            data_size,
+            sample_count,
+            table,
+        })
+    }
+    fn write<W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        out.write_all(&DS64_HEADER)?;
+        out.write_u32::<LittleEndian>(28 + 12 * self.table.len() as u32)?;
+        out.write_u64::<LittleEndian>(self.riff_size)?;
+        out.write_u64::<LittleEndian>(self.data_size)?;
+        out.write_u64::<LittleEndian>(self.sample_count)?;
+        out.write_u32::<LittleEndian>(self.table.len() as u32)?;
+        for (id, size) in self.table.iter() {
+            out.write_all(id)?;
+            out.write_u64::<LittleEndian>(*size)?;
+        }
+        Ok(())
+    }
+    fn chunk_size(&self, id: [u8; 4]) -> Option<u64> {
+        if id == DATA_HEADER {
+            return Some(self.data_size);
+        }
+        self.table
+            .iter()
+            .find(|(table_id, _)| *table_id == id)
+            .map(|(_, size)| *size)
+    }
 }
 
 #[derive(Debug)]
 pub struct WavFile {
-    riff_header: [u8; 4],
-    file_size: u32,
+    container: Container,
+    file_size: u64,
     wave_header: [u8; 4],
     fmt_chunk: FmtChunk,
     fact_chunk: Option<FactChunk>,
     peak_chunk: Option<PeakChunk>,
     data_chunk: DataChunk,
+    metadata: Metadata,
+    extra_chunks: Vec<RawChunk>,
+    extra_chunks_before_data: usize,
 }
 
-fn parse_chunk<'a, T: Read + Seek>(data: &'a mut T) -> Result<RiffChunk, anyhow::Error> {
-    let mut header = [0u8; 4];
-    data.read(&mut header)?;
+struct ChunkHeader {
+    id: [u8; 4],
+    size: u64,
+    // stream position of the chunk body
+    start: u64,
+    // bytes between the body and the end of the stream
+    bytes_left: u64,
+}
 
-    match header {
-        FMT_HEADER => Ok(RiffChunk::Fmt(FmtChunk::read(data, header)?)),
-        PEAK_HEADER => Ok(RiffChunk::Peak(PeakChunk::read(data, header)?)),
-        FACT_HEADER => Ok(RiffChunk::Fact(FactChunk::read(data, header)?)),
-        DATA_HEADER => Ok(RiffChunk::Data(DataChunk::read(data, header)?)),
-        _ => {
-            return Err(anyhow!("Unsupported header found"));
-        }
+impl ChunkHeader {
+    // stream position of the chunk id
+    fn offset(&self) -> u64 {
+        self.start - 8
+    }
+
+    // runs `read` over just the chunk body, so reading past the declared size
+    // reports the chunk as truncated instead of eating into the next one
+    fn read_body<R: Read, C>(
+        &self,
+        data: R,
+        read: impl FnOnce(&mut Take<R>, &ChunkHeader) -> Result<C, WavError>,
+    ) -> Result<C, WavError> {
+        read(&mut data.take(self.size), self)
+            .map_err(|error| error.truncated_at(self.id, self.offset()))
     }
 }
 
-fn bytes_remaining<T: Read + Seek>(data: &mut T) -> Result<u64, anyhow::Error> {
-    let old_pos = data.stream_position()?;
-    let end = data.seek(SeekFrom::End(0))?;
-    let bytes_remaining = end - old_pos;
-    // Avoid seeking a third time when we were already at the end of the
-    // stream. The branch is usually way cheaper than a seek operation.
-    if old_pos != end {
-        data.seek(SeekFrom::Start(old_pos))?;
+fn read_chunk_header<E: ByteOrder, T: Read + Seek>(
+    data: &mut T,
+    ds64: Option<&Ds64Chunk>,
+) -> Result<ChunkHeader, WavError> {
+    let bounds =
+        read_chunk_bounds::<E, _, WavError>(data, |id, offset, size| match (size, ds64) {
+            (u32::MAX, Some(ds64)) => ds64
+                .chunk_size(id)
+                .ok_or(WavError::MissingDs64Size { chunk: id, offset }),
+            (size, _) => Ok(size as u64),
+        })?;
+    Ok(ChunkHeader {
+        id: bounds.id,
+        size: bounds.size,
+        start: bounds.offset + 8,
+        bytes_left: bounds.bytes_left,
+    })
+}
+
+// seeks past the chunk body plus the pad byte of odd sized chunks, which some
+// writers leave out at the very end of the file
+fn skip_chunk<T: Read + Seek>(data: &mut T, header: &ChunkHeader) -> Result<(), WavError> {
+    let padded_size = header.size + header.size % 2;
+    data.seek(SeekFrom::Start(
+        header.start + padded_size.min(header.bytes_left),
+    ))?;
+    Ok(())
+}
+
+fn parse_chunk<E: ByteOrder, T: Read + Seek>(
+    data: &mut T,
+    ds64: Option<&Ds64Chunk>,
+) -> Result<RiffChunk, WavError> {
+    let header = read_chunk_header::<E, _>(data, ds64)?;
+
+    let chunk = match header.id {
+        FMT_HEADER => RiffChunk::Fmt(header.read_body(&mut *data, FmtChunk::read::<E, _>)?),
+        PEAK_HEADER => RiffChunk::Peak(header.read_body(&mut *data, PeakChunk::read::<E, _>)?),
+        FACT_HEADER => RiffChunk::Fact(header.read_body(&mut *data, FactChunk::read::<E, _>)?),
+        DATA_HEADER => RiffChunk::Data(header.read_body(&mut *data, DataChunk::read)?),
+        BEXT_HEADER => RiffChunk::Bext(header.read_body(&mut *data, Bext::read::<E, _>)?),
+        LIST_HEADER => read_list::<E, _>(data, &header)?,
+        SMPL_HEADER => RiffChunk::Sampler(header.read_body(&mut *data, Sampler::read::<E, _>)?),
+        CUE_HEADER => RiffChunk::Cue(header.read_body(&mut *data, CuePoint::read_list::<E, _>)?),
+        _ => RiffChunk::Raw(header.read_body(&mut *data, RawChunk::read)?),
+    };
+
+    skip_chunk(data, &header)?;
+    Ok(chunk)
+}
+
+// LIST chunks other than INFO (such as adtl) are kept raw
+fn read_list<E: ByteOrder, T: Read>(
+    data: &mut T,
+    header: &ChunkHeader,
+) -> Result<RiffChunk, WavError> {
+    let raw = header.read_body(data, RawChunk::read)?;
+    if !raw.data.starts_with(&INFO_HEADER) {
+        return Ok(RiffChunk::Raw(raw));
     }
-    Ok(bytes_remaining)
+    Ok(RiffChunk::Info(
+        header.read_body(&raw.data[..], InfoList::read::<E, _>)?,
+    ))
 }
 
-impl WavFile {
-    pub fn from_bytes<T: Read + Seek>(data: &mut T) -> Result<Self, anyhow::Error> {
-        let mut riff_header = [0u8; 4];
+struct RiffHeader {
+    file_size: u64,
+    wave_header: [u8; 4],
+    ds64: Option<Ds64Chunk>,
+}
 
-        data.read_exact(&mut riff_header)?;
-        let data_len = data.read_u32::<LittleEndian>()?;
+fn read_container<T: Read + Seek>(data: &mut T) -> Result<Container, WavError> {
+    let available = bytes_remaining::<WavError, _>(data)?;
+    let mut riff_header = [0u8; 4];
+    data.read_exact(&mut riff_header[..available.min(4) as usize])?;
+    Container::from_header(riff_header).ok_or(WavError::BadRiffHeader(riff_header))
+}
 
-        let bytes_left = bytes_remaining(data)?;
+// everything between the container id and the first regular chunk
+fn read_riff_header<E: ByteOrder, T: Read + Seek>(
+    data: &mut T,
+    container: Container,
+) -> Result<RiffHeader, WavError> {
+    let offset = data.stream_position()? - 4;
+    let truncated =
+        |error: std::io::Error| WavError::from(error).truncated_at(container.header(), offset);
 
-        if data_len as u64 != bytes_left {
-            return Err(anyhow!("file size different from size specified in header"));
-        };
-        let mut wave_header = [0u8; 4];
+    let data_len = data.read_u32::<E>().map_err(truncated)?;
 
-        data.read_exact(&mut wave_header)?;
+    let bytes_left = bytes_remaining::<WavError, _>(data)?;
 
-        if riff_header != RIFF_HEADER {
-            return Err(anyhow!("bad RIFF header"));
+    let mut wave_header = [0u8; 4];
+
+    data.read_exact(&mut wave_header).map_err(truncated)?;
+
+    if wave_header != WAVE_HEADER {
+        return Err(WavError::BadWaveHeader(wave_header));
+    }
+
+    let ds64 = if container.has_ds64() {
+        let header = read_chunk_header::<LittleEndian, _>(data, None)?;
+        if header.id != DS64_HEADER {
+            return Err(WavError::MissingDs64 {
+                offset: header.offset(),
+            });
         }
+        let ds64 = header.read_body(&mut *data, Ds64Chunk::read)?;
+        skip_chunk(data, &header)?;
+        Some(ds64)
+    } else {
+        None
+    };
+
+    let file_size = match (&ds64, data_len) {
+        (Some(ds64), u32::MAX) => ds64.riff_size,
+        _ => data_len as u64,
+    };
+    if file_size != bytes_left {
+        return Err(WavError::FileSizeMismatch {
+            expected: file_size,
+            actual: bytes_left,
+        });
+    };
+    Ok(RiffHeader {
+        file_size,
+        wave_header,
+        ds64,
+    })
+}
 
-        if wave_header != WAVE_HEADER {
-            return Err(anyhow!("bad WAVE header"));
+fn decode_block(container: Container, fmt: &FmtChunk, data: &[u8]) -> Result<Box<[f32]>, WavError> {
+    let decode = match container {
+        Container::Rifx => decode::decode_samples::<BigEndian>,
+        _ => decode::decode_samples::<LittleEndian>,
+    };
+    decode(
+        fmt.sample_format()?,
+        fmt.valid_bits_per_sample(),
+        fmt.container_bytes()?,
+        data,
+    )
+}
+
+impl WavFile {
+    /// Parses RIFF, RIFX (big endian) and RF64/BW64 (64 bit sizes) WAVE files.
+    pub fn from_bytes<T: Read + Seek>(data: &mut T) -> Result<Self, WavError> {
+        let container = read_container(data)?;
+        match container {
+            Container::Rifx => Self::read_chunks::<BigEndian, T>(data, container),
+            _ => Self::read_chunks::<LittleEndian, T>(data, container),
         }
+    }
+
+    fn read_chunks<E: ByteOrder, T: Read + Seek>(
+        data: &mut T,
+        container: Container,
+    ) -> Result<Self, WavError> {
+        let RiffHeader {
+            file_size,
+            wave_header,
+            ds64,
+        } = read_riff_header::<E, _>(data, container)?;
 
         let mut chunks: Vec<RiffChunk> = vec![];
-        while bytes_remaining(data)? > 0 {
-            chunks.push(parse_chunk(data)?);
+        while bytes_remaining::<WavError, _>(data)? > 0 {
+            chunks.push(parse_chunk::<E, _>(data, ds64.as_ref())?);
         }
 
         let mut fmt_chunk: Option<FmtChunk> = None;
         let mut data_chunk: Option<DataChunk> = None;
         let mut peak_chunk: Option<PeakChunk> = None;
         let mut fact_chunk: Option<FactChunk> = None;
+        let mut metadata = Metadata::default();
+        let mut extra_chunks: Vec<RawChunk> = vec![];
+        let mut extra_chunks_before_data = 0;
 
         for chunk in chunks.into_iter() {
             match chunk {
                 RiffChunk::Fmt(fmt) => fmt_chunk = Some(fmt),
-                RiffChunk::Data(data) => data_chunk = Some(data),
+                RiffChunk::Data(data) => {
+                    data_chunk = Some(data);
+                    extra_chunks_before_data = extra_chunks.len();
+                }
                 RiffChunk::Peak(peak) => peak_chunk = Some(peak),
                 RiffChunk::Fact(fact) => fact_chunk = Some(fact),
+                RiffChunk::Info(info) => info
+                    .iter()
+                    .for_each(|(id, value)| metadata.info.set(id, value)),
+                RiffChunk::Bext(bext) => metadata.bext = Some(bext),
+                RiffChunk::Sampler(sampler) => metadata.sampler = Some(sampler),
+                RiffChunk::Cue(cue_points) => metadata.cue_points = cue_points,
+                RiffChunk::Raw(raw) => extra_chunks.push(raw),
             }
         }
 
         Ok(Self {
-            riff_header,
-            file_size: data_len,
+            container,
+            file_size,
             wave_header,
-            fmt_chunk: fmt_chunk.ok_or(anyhow!("No fmt chunk"))?,
+            fmt_chunk: fmt_chunk.ok_or(WavError::MissingChunk(FMT_HEADER))?,
             fact_chunk,
             peak_chunk,
-            data_chunk: data_chunk.ok_or(anyhow!("No data chunk"))?,
+            data_chunk: data_chunk.ok_or(WavError::MissingChunk(DATA_HEADER))?,
+            metadata,
+            extra_chunks,
+            extra_chunks_before_data,
         })
     }
 
-    pub fn get_samples(&self) -> &[f32] {
-        let data = self.data_chunk.data.as_ref();
-        let a = unsafe { data.align_to::<f32>() };
-        a.1
+    /// Serializes the file in its original container, recomputing every size field.
+    pub fn write_to<W: Write + Seek>(&self, out: &mut W) -> Result<(), WavError> {
+        match self.container {
+            Container::Rifx => self.write_chunks::<BigEndian, W>(out),
+            _ => self.write_chunks::<LittleEndian, W>(out),
+        }
+    }
+
+    fn write_chunks<E: ByteOrder, W: Write + Seek>(&self, out: &mut W) -> Result<(), WavError> {
+        let start = out.stream_position()?;
+        out.write_all(&self.container.header())?;
+        out.write_u32::<E>(0)?;
+        out.write_all(&self.wave_header)?;
+        let ds64_position = if self.container.has_ds64() {
+            let position = out.stream_position()?;
+            Ds64Chunk::default().write(out)?;
+            Some(position)
+        } else {
+            None
+        };
+        self.fmt_chunk.write::<E, _>(out)?;
+        if let Some(fact_chunk) = &self.fact_chunk {
+            fact_chunk.write::<E, _>(out)?;
+        }
+        if let Some(peak_chunk) = &self.peak_chunk {
+            peak_chunk.write::<E, _>(out)?;
+        }
+        self.metadata.write::<E, _>(out)?;
+        let (before_data, after_data) = self
+            .extra_chunks
+            .split_at(self.extra_chunks_before_data.min(self.extra_chunks.len()));
+        for chunk in before_data {
+            chunk.write::<E, _>(out)?;
+        }
+        if ds64_position.is_some() {
+            self.data_chunk.write_rf64(out)?;
+        } else {
+            self.data_chunk.write::<E, _>(out)?;
+        }
+        for chunk in after_data {
+            chunk.write::<E, _>(out)?;
+        }
+        let end = out.stream_position()?;
+        let riff_size = end - start - 8;
+        out.seek(SeekFrom::Start(start + 4))?;
+        if let Some(position) = ds64_position {
+            out.write_u32::<E>(u32::MAX)?;
+            out.seek(SeekFrom::Start(position))?;
+            Ds64Chunk {
+                riff_size,
+                data_size: self.data_chunk.data.len() as u64,
+                sample_count: self.frame_count(),
+                table: vec![],
+            }
+            .write(out)?;
+        } else {
+            let riff_size =
+                u32::try_from(riff_size).map_err(|_| WavError::TooLarge { size: riff_size })?;
+            out.write_u32::<E>(riff_size)?;
+        }
+        out.seek(SeekFrom::Start(end))?;
+        Ok(())
+    }
+
+    pub fn container(&self) -> Container {
+        self.container
+    }
+
+    /// Number of sample frames (one sample per channel) in the data chunk.
+    pub fn frame_count(&self) -> u64 {
+        match self.fmt_chunk.block_align {
+            0 => 0,
+            block_align => self.data_chunk.data.len() as u64 / block_align as u64,
+        }
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        WavSpec {
+            format: self.fmt_chunk.format,
+            channels: self.fmt_chunk.channels,
+            sample_rate: self.fmt_chunk.sample_rate,
+            bits_per_sample: self.fmt_chunk.bits_per_sample,
+            peak_chunk: self.peak_chunk.is_some(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.fmt_chunk.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.fmt_chunk.channels
+    }
+
+    /// LIST/INFO tags, the Broadcast Wave `bext` chunk and sampler root note, loops and cues.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Metadata is written after the PEAK chunk and before the data by `write_to`.
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    /// Chunks other than fmt, fact, PEAK, data and those in `metadata()`, in file order.
+    pub fn extra_chunks(&self) -> &[RawChunk] {
+        &self.extra_chunks
+    }
+
+    /// Chunks pushed here are written after the data chunk by `write_to`.
+    pub fn extra_chunks_mut(&mut self) -> &mut Vec<RawChunk> {
+        &mut self.extra_chunks
+    }
+
+    /// Speaker assignment from a WAVE_FORMAT_EXTENSIBLE header, if the file has one.
+    pub fn channel_mask(&self) -> Option<ChannelMask> {
+        self.fmt_chunk
+            .extensible
+            .as_ref()
+            .map(|extensible| extensible.channel_mask)
+    }
+
+    /// Decodes the data chunk into interleaved samples normalized to `[-1.0, 1.0)`.
+    pub fn get_samples(&self) -> Result<Box<[f32]>, WavError> {
+        decode_block(self.container, &self.fmt_chunk, &self.data_chunk.data)
+    }
+
+    /// Decodes the data chunk into one buffer per channel.
+    pub fn get_channels(&self) -> Result<Box<[Box<[f32]>]>, WavError> {
+        deinterleave(&self.get_samples()?, self.fmt_chunk.channels)
+    }
+
+    /// Decodes the data chunk down to the single signal picked by `selection`.
+    pub fn get_mono_samples(&self, selection: ChannelSelection) -> Result<Box<[f32]>, WavError> {
+        selection.apply(&self.get_samples()?, self.fmt_chunk.channels)
     }
 }
 #[test]
 fn headers() {
     assert_eq!(str::from_utf8(&RIFF_HEADER).unwrap(), "RIFF");
     assert_eq!(str::from_utf8(&RIFX_HEADER).unwrap(), "RIFX");
+    assert_eq!(str::from_utf8(&RF64_HEADER).unwrap(), "RF64");
+    assert_eq!(str::from_utf8(&BW64_HEADER).unwrap(), "BW64");
+    assert_eq!(str::from_utf8(&DS64_HEADER).unwrap(), "ds64");
     assert_eq!(str::from_utf8(&PEAK_HEADER).unwrap(), "PEAK");
     assert_eq!(str::from_utf8(&FACT_HEADER).unwrap(), "fact");
     assert_eq!(str::from_utf8(&FMT_HEADER).unwrap(), "fmt ");
     assert_eq!(str::from_utf8(&WAVE_HEADER).unwrap(), "WAVE");
+    assert_eq!(str::from_utf8(&LIST_HEADER).unwrap(), "LIST");
+    assert_eq!(str::from_utf8(&INFO_HEADER).unwrap(), "INFO");
+    assert_eq!(str::from_utf8(&BEXT_HEADER).unwrap(), "bext");
+    assert_eq!(str::from_utf8(&SMPL_HEADER).unwrap(), "smpl");
+    assert_eq!(str::from_utf8(&CUE_HEADER).unwrap(), "cue ");
 }
 
 #[test]
@@ -349,3 +924,383 @@ fn file_read() {
 
     let wav = WavFile::from_bytes(&mut cursor).unwrap();
 }
+
+#[test]
+fn extensible_file() {
+    // 24 bit stereo WAVE_FORMAT_EXTENSIBLE, as exported by most DAWs
+    let mut bytes = vec![];
+    bytes.extend_from_slice(b"RIFF");
+    bytes.write_u32::<LittleEndian>(4 + 48 + 8 + 12).unwrap();
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.write_u32::<LittleEndian>(40).unwrap();
+    bytes.write_u16::<LittleEndian>(0xFFFE).unwrap();
+    bytes.write_u16::<LittleEndian>(2).unwrap();
+    bytes.write_u32::<LittleEndian>(48000).unwrap();
+    bytes.write_u32::<LittleEndian>(48000 * 6).unwrap();
+    bytes.write_u16::<LittleEndian>(6).unwrap();
+    bytes.write_u16::<LittleEndian>(24).unwrap();
+    bytes.write_u16::<LittleEndian>(22).unwrap();
+    bytes.write_u16::<LittleEndian>(24).unwrap();
+    bytes.write_u32::<LittleEndian>(0x3).unwrap();
+    bytes.extend_from_slice(&KSDATAFORMAT_SUBTYPE_PCM.0);
+    bytes.extend_from_slice(b"data");
+    bytes.write_u32::<LittleEndian>(12).unwrap();
+    for sample in [0x400000, -0x400000, 0, 0x200000] {
+        bytes.write_i24::<LittleEndian>(sample).unwrap();
+    }
+
+    let wav = WavFile::from_bytes(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(
+        &wav.channel_mask().unwrap().speakers()[..],
+        &[Speaker::FrontLeft, Speaker::FrontRight]
+    );
+    assert_eq!(&wav.get_samples().unwrap()[..], &[0.5, -0.5, 0.0, 0.25]);
+
+    let mut out = Cursor::new(vec![]);
+    wav.write_to(&mut out).unwrap();
+    assert_eq!(out.into_inner(), bytes);
+}
+
+#[test]
+fn unknown_chunks() {
+    let file = include_bytes!(".././A.wav");
+    let data_start = file.windows(4).position(|w| w == DATA_HEADER).unwrap();
+
+    // odd sized JUNK (with its pad byte) before the data, iXML after it
+    let mut bytes = file[..data_start].to_vec();
+    bytes.extend_from_slice(b"JUNK\x03\0\0\0abc\0");
+    bytes.extend_from_slice(&file[data_start..]);
+    bytes.extend_from_slice(b"iXML\x04\0\0\0<a/>");
+    let riff_size = bytes.len() as u32 - 8;
+    LittleEndian::write_u32(&mut bytes[4..8], riff_size);
+
+    let wav = WavFile::from_bytes(&mut Cursor::new(&bytes)).unwrap();
+    let original = WavFile::from_bytes(&mut Cursor::new(file)).unwrap();
+    assert_eq!(wav.extra_chunks().len(), 2);
+    assert_eq!(&wav.extra_chunks()[0].id, b"JUNK");
+    assert_eq!(&wav.extra_chunks()[0].data[..], b"abc");
+    assert_eq!(&wav.extra_chunks()[1].data[..], b"<a/>");
+    assert_eq!(wav.get_samples().unwrap(), original.get_samples().unwrap());
+
+    let mut out = Cursor::new(vec![]);
+    wav.write_to(&mut out).unwrap();
+    assert_eq!(out.into_inner(), bytes);
+}
+
+#[test]
+fn rifx_file() {
+    // 16 bit mono PCM with every field big endian
+    let mut bytes = vec![];
+    bytes.extend_from_slice(b"RIFX");
+    bytes.write_u32::<BigEndian>(4 + 24 + 8 + 6).unwrap();
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.write_u32::<BigEndian>(16).unwrap();
+    bytes.write_u16::<BigEndian>(1).unwrap();
+    bytes.write_u16::<BigEndian>(1).unwrap();
+    bytes.write_u32::<BigEndian>(44100).unwrap();
+    bytes.write_u32::<BigEndian>(88200).unwrap();
+    bytes.write_u16::<BigEndian>(2).unwrap();
+    bytes.write_u16::<BigEndian>(16).unwrap();
+    bytes.extend_from_slice(b"data");
+    bytes.write_u32::<BigEndian>(6).unwrap();
+    for sample in [0x4000, -0x4000, 0x2000] {
+        bytes.write_i16::<BigEndian>(sample).unwrap();
+    }
+
+    let wav = WavFile::from_bytes(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(wav.container(), Container::Rifx);
+    assert_eq!(wav.sample_rate(), 44100);
+    assert_eq!(&wav.get_samples().unwrap()[..], &[0.5, -0.5, 0.25]);
+
+    let mut out = Cursor::new(vec![]);
+    wav.write_to(&mut out).unwrap();
+    assert_eq!(out.into_inner(), bytes);
+}
+
+#[test]
+fn rf64_file() {
+    let file = include_bytes!(".././A.wav");
+    let original = WavFile::from_bytes(&mut Cursor::new(file)).unwrap();
+    let data_start = file.windows(4).position(|w| w == DATA_HEADER).unwrap();
+
+    // same file with its sizes moved into a ds64 chunk
+    let mut bytes = vec![];
+    bytes.extend_from_slice(b"RF64");
+    bytes.write_u32::<LittleEndian>(u32::MAX).unwrap();
+    bytes.extend_from_slice(b"WAVE");
+    Ds64Chunk {
+        riff_size: file.len() as u64 - 8 + 36,
+        data_size: file.len() as u64 - data_start as u64 - 8,
+        sample_count: original.frame_count(),
+        table: vec![],
+    }
+    .write(&mut bytes)
+    .unwrap();
+    bytes.extend_from_slice(&file[12..data_start]);
+    bytes.extend_from_slice(b"data");
+    bytes.write_u32::<LittleEndian>(u32::MAX).unwrap();
+    bytes.extend_from_slice(&file[data_start + 8..]);
+
+    let wav = WavFile::from_bytes(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(wav.container(), Container::Rf64);
+    assert_eq!(wav.frame_count(), original.frame_count());
+    assert_eq!(wav.get_samples().unwrap(), original.get_samples().unwrap());
+
+    let mut out = Cursor::new(vec![]);
+    wav.write_to(&mut out).unwrap();
+    assert_eq!(out.into_inner(), bytes);
+}
+
+#[test]
+fn corrupt_files() {
+    // every path a caller might take through a file, none of which may panic
+    fn parse(bytes: &[u8]) -> Result<(), WavError> {
+        if let Result::Ok(mut reader) = WavReader::new(Cursor::new(bytes)) {
+            for block in reader.blocks(64) {
+                block?;
+            }
+        }
+        let wav = WavFile::from_bytes(&mut Cursor::new(bytes))?;
+        wav.get_channels()?;
+        wav.get_mono_samples(ChannelSelection::Downmix)?;
+        wav.write_to(&mut Cursor::new(vec![]))
+    }
+    fn fix_riff_size(bytes: &mut [u8]) {
+        if bytes.len() >= 8 {
+            let riff_size = bytes.len() as u32 - 8;
+            LittleEndian::write_u32(&mut bytes[4..8], riff_size);
+        }
+    }
+
+    // A.wav cut down to a few hundred samples, so each mutation parses quickly
+    let file = include_bytes!(".././A.wav");
+    let data_start = file.windows(4).position(|w| w == DATA_HEADER).unwrap();
+    let mut base = file[..data_start + 8 + 1024].to_vec();
+    LittleEndian::write_u32(&mut base[data_start + 4..data_start + 8], 1024);
+    fix_riff_size(&mut base);
+    parse(&base).unwrap();
+
+    let mut corpus = vec![];
+    for len in 0..base.len() {
+        let mut truncated = base[..len].to_vec();
+        corpus.push(truncated.clone());
+        fix_riff_size(&mut truncated);
+        corpus.push(truncated);
+    }
+    for position in 0..data_start + 8 {
+        for value in [0x00, 0x01, 0x7F, 0x80, 0xFE, 0xFF] {
+            let mut mutated = base.clone();
+            mutated[position] = value;
+            corpus.push(mutated);
+        }
+    }
+    // deterministic pseudo-random byte mutations of the header region
+    let mut state = 0x2545F491u32;
+    let mut next = || {
+        state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+        state >> 8
+    };
+    for _ in 0..2000 {
+        let mut mutated = base.clone();
+        for _ in 0..1 + next() % 4 {
+            let position = next() as usize % (data_start + 8);
+            mutated[position] = next() as u8;
+        }
+        corpus.push(mutated);
+    }
+
+    for bytes in corpus.iter() {
+        let _ = parse(bytes);
+    }
+
+    let error = |bytes: &[u8]| WavFile::from_bytes(&mut Cursor::new(bytes)).unwrap_err();
+    assert!(matches!(error(&base[..2]), WavError::BadRiffHeader(_)));
+    assert!(matches!(
+        error(&base[..6]),
+        WavError::Truncated {
+            chunk: RIFF_HEADER,
+            offset: 0
+        }
+    ));
+    let mut bytes = base.clone();
+    bytes[8..12].copy_from_slice(b"AVI ");
+    assert!(matches!(error(&bytes), WavError::BadWaveHeader(_)));
+    assert!(matches!(
+        error(&base[..base.len() - 1]),
+        WavError::FileSizeMismatch { .. }
+    ));
+
+    // fmt cut off in the middle of its fields
+    let mut bytes = base[..30].to_vec();
+    LittleEndian::write_u32(&mut bytes[16..20], 10);
+    fix_riff_size(&mut bytes);
+    assert!(matches!(
+        error(&bytes),
+        WavError::Truncated {
+            chunk: FMT_HEADER,
+            offset: 12
+        }
+    ));
+
+    // fmt declaring more extra data than it holds
+    let mut bytes = base[..36].to_vec();
+    LittleEndian::write_u32(&mut bytes[16..20], 20);
+    bytes.extend_from_slice(&[0xFF, 0xFF, 0, 0]);
+    fix_riff_size(&mut bytes);
+    assert!(matches!(
+        error(&bytes),
+        WavError::Truncated {
+            chunk: FMT_HEADER,
+            offset: 12
+        }
+    ));
+
+    let mut bytes = base.clone();
+    LittleEndian::write_u16(&mut bytes[20..22], 0x1234);
+    assert!(matches!(
+        error(&bytes),
+        WavError::UnknownFormat {
+            offset: 12,
+            tag: 0x1234
+        }
+    ));
+
+    let mut bytes = base.clone();
+    LittleEndian::write_u32(&mut bytes[data_start + 4..data_start + 8], u32::MAX - 1);
+    assert!(matches!(
+        error(&bytes),
+        WavError::ChunkOverrun {
+            chunk: DATA_HEADER,
+            size: 0xFFFFFFFE,
+            available: 1024,
+            ..
+        }
+    ));
+
+    let mut bytes = base.clone();
+    bytes[data_start..data_start + 4].copy_from_slice(b"junk");
+    assert!(matches!(error(&bytes), WavError::MissingChunk(DATA_HEADER)));
+
+    // three stray bytes after the data chunk
+    let mut bytes = base.clone();
+    bytes.extend_from_slice(b"LIS");
+    fix_riff_size(&mut bytes);
+    assert!(matches!(error(&bytes), WavError::Truncated { chunk, .. } if chunk == *b"LIS\0"));
+
+    let mut bytes = base.clone();
+    LittleEndian::write_u16(&mut bytes[32..34], 0);
+    let wav = WavFile::from_bytes(&mut Cursor::new(&bytes)).unwrap();
+    assert!(matches!(
+        wav.get_samples().unwrap_err(),
+        WavError::InvalidBlockAlign { block_align: 0, .. }
+    ));
+    assert!(WavReader::new(Cursor::new(&bytes)).is_err());
+}
+
+#[test]
+fn metadata() {
+    // a practice take tagged the way the recorder does it
+    let mut metadata = Metadata::default();
+    metadata.info.set(INFO_NAME, "Standard tuning, low E");
+    metadata.info.set(INFO_COMMENT, "E A D G B E");
+    metadata.info.set(INFO_CREATION_DATE, "2024-05-01");
+    metadata.bext = Some(Bext {
+        description: "Guitar".into(),
+        originator: "tuner".into(),
+        origination_date: "2024-05-01".into(),
+        origination_time: "18:30:00".into(),
+        time_reference: 48000 * 3600 * 18 + (1 << 33),
+        coding_history: "A=PCM,F=48000,W=16,M=mono\r\n".into(),
+        ..Bext::default()
+    });
+    let spec = WavSpec {
+        format: WavFormat::PCM,
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample: 16,
+        peak_chunk: false,
+    };
+    let mut writer = WavWriter::with_metadata(Cursor::new(vec![]), spec, &metadata).unwrap();
+    writer.write_samples(&[0.0, 0.5, -0.5]).unwrap();
+    let bytes = writer.finalize().unwrap().into_inner();
+
+    let wav = WavFile::from_bytes(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(wav.metadata(), &metadata);
+    assert!(wav.extra_chunks().is_empty());
+    assert_eq!(&wav.get_samples().unwrap()[..], &[0.0, 0.5, -0.5]);
+    let reader = WavReader::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(reader.metadata(), &metadata);
+
+    let mut out = Cursor::new(vec![]);
+    wav.write_to(&mut out).unwrap();
+    assert_eq!(out.into_inner(), bytes);
+
+    // INFO after the data is picked up too, other LIST types stay raw
+    let file = include_bytes!(".././A.wav");
+    let mut bytes = file.to_vec();
+    bytes.extend_from_slice(b"LIST\x16\0\0\0INFOISFT\x09\0\0\0Audacity\0\0");
+    bytes.extend_from_slice(b"LIST\x04\0\0\0adtl");
+    let riff_size = bytes.len() as u32 - 8;
+    LittleEndian::write_u32(&mut bytes[4..8], riff_size);
+    let mut wav = WavFile::from_bytes(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(wav.metadata().info.get(INFO_SOFTWARE), Some("Audacity"));
+    assert_eq!(wav.metadata().bext, None);
+    assert_eq!(&wav.extra_chunks()[0].data[..], b"adtl");
+
+    wav.metadata_mut().info.set(INFO_SOFTWARE, "tuner");
+    let mut out = Cursor::new(vec![]);
+    wav.write_to(&mut out).unwrap();
+    out.set_position(0);
+    let rewritten = WavFile::from_bytes(&mut out).unwrap();
+    assert_eq!(rewritten.metadata().info.get(INFO_SOFTWARE), Some("tuner"));
+}
+
+#[test]
+fn sampler_chunks() {
+    use crate::audio_analysis::{AudioAnalyzer, Note, WindowType};
+
+    let file = include_bytes!(".././A.wav");
+    let mut wav = WavFile::from_bytes(&mut Cursor::new(file)).unwrap();
+    assert_eq!(wav.metadata().sampler, None);
+
+    // what the root_note tool does: detect the pitch and store it (A.wav is a pure
+    // sine, so without the harmonic product spectrum)
+    let mut analyzer = AudioAnalyzer::new(48000, 1 << 16, 0, 3, 440, WindowType::Hann);
+    analyzer.add_samples(&wav.get_samples().unwrap());
+    let note = Note::freq_to_number(analyzer.strongest_freq(), 440);
+    let mut sampler = Sampler::new(wav.sample_rate(), note);
+    sampler.loops.push(SampleLoop {
+        start: 100,
+        end: 4899,
+        ..SampleLoop::default()
+    });
+    wav.metadata_mut().sampler = Some(sampler);
+    wav.metadata_mut().cue_points.push(CuePoint {
+        id: 1,
+        position: 0,
+        data_chunk_id: DATA_HEADER,
+        chunk_start: 0,
+        block_start: 0,
+        sample_offset: 100,
+    });
+
+    let mut out = Cursor::new(vec![]);
+    wav.write_to(&mut out).unwrap();
+    out.set_position(0);
+    let tagged = WavFile::from_bytes(&mut out).unwrap();
+    assert_eq!(tagged.metadata(), wav.metadata());
+    let sampler = tagged.metadata().sampler.as_ref().unwrap();
+    assert_eq!(sampler.midi_unity_note, 69);
+    assert!((sampler.root_note() - 69.0).abs() < 0.05);
+    assert_eq!(sampler.loops[0].end, 4899);
+    assert_eq!(tagged.metadata().cue_points[0].sample_offset, 100);
+
+    // a loop count larger than the chunk
+    let mut bytes = out.into_inner();
+    let smpl = bytes.windows(4).position(|w| w == SMPL_HEADER).unwrap();
+    LittleEndian::write_u32(&mut bytes[smpl + 36..smpl + 40], u32::MAX);
+    assert!(matches!(
+        WavFile::from_bytes(&mut Cursor::new(&bytes)).unwrap_err(),
+        WavError::Truncated { chunk: SMPL_HEADER, offset } if offset == smpl as u64
+    ));
+}
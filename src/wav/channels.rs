@@ -0,0 +1,121 @@
+use std::iter::{Skip, StepBy};
+
+use super::WavError;
+
+/// Which signal to hand to a mono consumer such as `AudioAnalyzer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelSelection {
+    /// a single channel by index
+    Channel(u16),
+    /// the average of every channel
+    Downmix,
+}
+
+impl ChannelSelection {
+    pub fn apply(self, samples: &[f32], channels: u16) -> Result<Box<[f32]>, WavError> {
+        match self {
+            ChannelSelection::Channel(channel) => {
+                Ok(channel_iter(samples, channels, channel)?.cloned().collect())
+            }
+            ChannelSelection::Downmix => downmix(samples, channels),
+        }
+    }
+
+    /// Like `apply`, replacing the contents of `output` so that its capacity is reused.
+    pub fn apply_into(
+        self,
+        samples: &[f32],
+        channels: u16,
+        output: &mut Vec<f32>,
+    ) -> Result<(), WavError> {
+        output.clear();
+        match self {
+            ChannelSelection::Channel(channel) => {
+                output.extend(channel_iter(samples, channels, channel)?);
+            }
+            ChannelSelection::Downmix => {
+                check_channels(channels)?;
+                let scale = 1.0 / channels as f32;
+                output.extend(
+                    samples
+                        .chunks_exact(channels as usize)
+                        .map(|frame| frame.iter().sum::<f32>() * scale),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_channels(channels: u16) -> Result<(), WavError> {
+    if channels == 0 {
+        return Err(WavError::InvalidChannel {
+            channel: 0,
+            channels,
+        });
+    }
+    Ok(())
+}
+
+/// Iterates over one channel of interleaved samples.
+pub fn channel_iter(
+    samples: &[f32],
+    channels: u16,
+    channel: u16,
+) -> Result<StepBy<Skip<std::slice::Iter<'_, f32>>>, WavError> {
+    check_channels(channels)?;
+    if channel >= channels {
+        return Err(WavError::InvalidChannel { channel, channels });
+    }
+    Ok(samples
+        .iter()
+        .skip(channel as usize)
+        .step_by(channels as usize))
+}
+
+/// Splits interleaved samples into one buffer per channel; a trailing partial frame is dropped.
+pub fn deinterleave(samples: &[f32], channels: u16) -> Result<Box<[Box<[f32]>]>, WavError> {
+    check_channels(channels)?;
+    let frames = samples.chunks_exact(channels as usize);
+    Ok((0..channels as usize)
+        .map(|channel| frames.clone().map(|frame| frame[channel]).collect())
+        .collect())
+}
+
+/// Averages every frame of interleaved samples down to a single channel.
+pub fn downmix(samples: &[f32], channels: u16) -> Result<Box<[f32]>, WavError> {
+    let mut output = Vec::with_capacity(samples.len() / channels.max(1) as usize);
+    ChannelSelection::Downmix.apply_into(samples, channels, &mut output)?;
+    Ok(output.into_boxed_slice())
+}
+
+#[test]
+fn splitting_channels() {
+    let samples = [0.0, 1.0, 0.5, -1.0, 0.25, 0.5];
+    let split = deinterleave(&samples, 2).unwrap();
+    assert_eq!(&split[0][..], &[0.0, 0.5, 0.25]);
+    assert_eq!(&split[1][..], &[1.0, -1.0, 0.5]);
+    assert_eq!(&downmix(&samples, 2).unwrap()[..], &[0.5, -0.25, 0.375]);
+    assert_eq!(
+        channel_iter(&samples, 2, 1)
+            .unwrap()
+            .cloned()
+            .collect::<Vec<_>>(),
+        [1.0, -1.0, 0.5]
+    );
+    assert_eq!(
+        &ChannelSelection::Channel(0).apply(&samples, 3).unwrap()[..],
+        &[0.0, -1.0]
+    );
+    let mut output = vec![9.0; 8];
+    ChannelSelection::Downmix
+        .apply_into(&samples, 2, &mut output)
+        .unwrap();
+    assert_eq!(&output[..], &[0.5, -0.25, 0.375]);
+    ChannelSelection::Channel(1)
+        .apply_into(&samples, 2, &mut output)
+        .unwrap();
+    assert_eq!(&output[..], &[1.0, -1.0, 0.5]);
+    assert!(channel_iter(&samples, 2, 2).is_err());
+    assert!(deinterleave(&samples, 0).is_err());
+}
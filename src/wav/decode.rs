@@ -0,0 +1,114 @@
+use byteorder::ByteOrder;
+
+use super::{WavError, WavFormat};
+
+/// Converts raw interleaved sample data into f32 samples normalized to `[-1.0, 1.0)`.
+///
+/// `container_bytes` is the storage size of one sample (`block_align / channels`),
+/// which can be wider than `bits_per_sample` for left-justified PCM such as 20-in-24.
+pub(super) fn decode_samples<E: ByteOrder>(
+    format: WavFormat,
+    bits_per_sample: u16,
+    container_bytes: usize,
+    data: &[u8],
+) -> Result<Box<[f32]>, WavError> {
+    if bits_per_sample == 0 || bits_per_sample as usize > container_bytes * 8 {
+        return Err(WavError::UnsupportedFormat {
+            format,
+            bits_per_sample,
+        });
+    }
+    let samples = data.chunks_exact(container_bytes);
+    let decoded = match (format, container_bytes) {
+        (WavFormat::PCM, 1) => samples
+            .map(|s| (s[0] as i16 - 128) as f32 / 128.0)
+            .collect(),
+        (WavFormat::PCM, 2) => samples.map(|s| E::read_i16(s) as f32 / 32768.0).collect(),
+        (WavFormat::PCM, 3) => samples.map(|s| E::read_i24(s) as f32 / 8388608.0).collect(),
+        (WavFormat::PCM, 4) => samples
+            .map(|s| (E::read_i32(s) as f64 / 2147483648.0) as f32)
+            .collect(),
+        (WavFormat::Float, 4) => samples.map(E::read_f32).collect(),
+        (WavFormat::Float, 8) => samples.map(|s| E::read_f64(s) as f32).collect(),
+        (WavFormat::ALaw, 1) => samples
+            .map(|s| alaw_to_linear(s[0]) as f32 / 32768.0)
+            .collect(),
+        (WavFormat::MuLaw, 1) => samples
+            .map(|s| mulaw_to_linear(s[0]) as f32 / 32768.0)
+            .collect(),
+        (format, _) => {
+            return Err(WavError::UnsupportedFormat {
+                format,
+                bits_per_sample,
+            })
+        }
+    };
+    Ok(decoded)
+}
+
+// ITU-T G.711 expansion to 16 bit linear PCM
+fn alaw_to_linear(value: u8) -> i16 {
+    let value = value ^ 0x55;
+    let exponent = (value >> 4) & 0x07;
+    let mantissa = (value & 0x0F) as i16;
+    let mut magnitude = (mantissa << 4) + 8;
+    if exponent != 0 {
+        magnitude = (magnitude + 0x100) << (exponent - 1);
+    }
+    if value & 0x80 != 0 {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+fn mulaw_to_linear(value: u8) -> i16 {
+    const BIAS: i16 = 0x84;
+    let value = !value;
+    let exponent = (value >> 4) & 0x07;
+    let mantissa = (value & 0x0F) as i16;
+    let magnitude = (((mantissa << 3) + BIAS) << exponent) - BIAS;
+    if value & 0x80 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+#[test]
+fn companding() {
+    // silence
+    assert_eq!(alaw_to_linear(0xD5), 8);
+    assert_eq!(alaw_to_linear(0x55), -8);
+    assert_eq!(mulaw_to_linear(0xFF), 0);
+    assert_eq!(mulaw_to_linear(0x7F), 0);
+    // full scale
+    assert_eq!(alaw_to_linear(0xAA), 32256);
+    assert_eq!(alaw_to_linear(0x2A), -32256);
+    assert_eq!(mulaw_to_linear(0x80), 32124);
+    assert_eq!(mulaw_to_linear(0x00), -32124);
+}
+
+#[test]
+fn integer_pcm() {
+    let data = [0x00, 0x80, 0xFF, 0x7F, 0x00, 0x40];
+    let decoded = decode_samples::<byteorder::LittleEndian>(WavFormat::PCM, 16, 2, &data).unwrap();
+    assert_eq!(&decoded[..], &[-1.0, 32767.0 / 32768.0, 0.5]);
+
+    let decoded = decode_samples::<byteorder::LittleEndian>(WavFormat::PCM, 24, 3, &data).unwrap();
+    assert_eq!(&decoded[..], &[-32768.0 / 8388608.0, 4194431.0 / 8388608.0]);
+
+    let decoded =
+        decode_samples::<byteorder::LittleEndian>(WavFormat::PCM, 8, 1, &[0, 128, 192]).unwrap();
+    assert_eq!(&decoded[..], &[-1.0, 0.0, 0.5]);
+}
+
+#[test]
+fn unsupported_combinations() {
+    assert!(decode_samples::<byteorder::LittleEndian>(WavFormat::Float, 16, 2, &[0; 4]).is_err());
+    assert!(decode_samples::<byteorder::LittleEndian>(WavFormat::ALaw, 16, 2, &[0; 4]).is_err());
+    assert!(decode_samples::<byteorder::LittleEndian>(WavFormat::PCM, 24, 2, &[0; 4]).is_err());
+    assert!(
+        decode_samples::<byteorder::LittleEndian>(WavFormat::Extensible, 16, 2, &[0; 4]).is_err()
+    );
+}
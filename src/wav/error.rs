@@ -0,0 +1,232 @@
+use std::{error::Error, fmt, io};
+
+use super::{Guid, WavFormat};
+use crate::chunk::ChunkError;
+
+/// Everything that can go wrong reading or writing a WAVE file.
+///
+/// `offset` fields are byte offsets from the start of the stream to the id of the
+/// chunk the problem was found in.
+#[derive(Debug)]
+pub enum WavError {
+    Io(io::Error),
+    /// the stream does not start with RIFF, RIFX, RF64 or BW64
+    BadRiffHeader([u8; 4]),
+    /// the form type after the RIFF size is not WAVE
+    BadWaveHeader([u8; 4]),
+    /// the RIFF size disagrees with the length of the stream
+    FileSizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    /// an RF64/BW64 file whose first chunk is not ds64
+    MissingDs64 {
+        offset: u64,
+    },
+    /// an RF64/BW64 chunk with a placeholder size the ds64 chunk doesn't cover
+    MissingDs64Size {
+        chunk: [u8; 4],
+        offset: u64,
+    },
+    /// a chunk claims more bytes than are left in the stream
+    ChunkOverrun {
+        chunk: [u8; 4],
+        offset: u64,
+        size: u64,
+        available: u64,
+    },
+    /// a chunk (or the stream) ended before all of its fields were read
+    Truncated {
+        chunk: [u8; 4],
+        offset: u64,
+    },
+    /// a chunk's contents contradict themselves
+    InvalidChunk {
+        chunk: [u8; 4],
+        offset: u64,
+        reason: &'static str,
+    },
+    /// a chunk every WAVE file needs is absent
+    MissingChunk([u8; 4]),
+    /// the fmt chunk names a format tag this module doesn't know
+    UnknownFormat {
+        offset: u64,
+        tag: u16,
+    },
+    /// a known format with a sample size that can't be decoded or encoded
+    UnsupportedFormat {
+        format: WavFormat,
+        bits_per_sample: u16,
+    },
+    /// a WAVE_FORMAT_EXTENSIBLE sub-format other than the standard ones
+    UnsupportedSubFormat(Guid),
+    /// the block align is not a whole number of bytes per channel
+    InvalidBlockAlign {
+        block_align: u16,
+        channels: u16,
+    },
+    /// a channel index at or above the channel count, or a channel count of zero
+    InvalidChannel {
+        channel: u16,
+        channels: u16,
+    },
+    /// a seek past the last frame of the data chunk
+    SeekOutOfRange {
+        frame: u64,
+        frame_count: u64,
+    },
+    /// the data doesn't fit the 32 bit size fields of the container
+    TooLarge {
+        size: u64,
+    },
+    /// a spec whose block align or byte rate overflows the fmt chunk fields
+    SpecOverflow {
+        channels: u16,
+        sample_rate: u32,
+        bits_per_sample: u16,
+    },
+}
+
+fn chunk_name(chunk: &[u8; 4]) -> String {
+    String::from_utf8_lossy(chunk).into_owned()
+}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WavError::Io(error) => write!(f, "io error: {}", error),
+            WavError::BadRiffHeader(found) => write!(f, "bad RIFF header {:?}", chunk_name(found)),
+            WavError::BadWaveHeader(found) => write!(f, "bad WAVE header {:?}", chunk_name(found)),
+            WavError::FileSizeMismatch { expected, actual } => write!(
+                f,
+                "header says {} bytes follow the RIFF size but the stream has {}",
+                expected, actual
+            ),
+            WavError::MissingDs64 { offset } => {
+                write!(f, "expected a ds64 chunk at byte {}", offset)
+            }
+            WavError::MissingDs64Size { chunk, offset } => write!(
+                f,
+                "{:?} chunk at byte {} has no size in the ds64 chunk",
+                chunk_name(chunk),
+                offset
+            ),
+            WavError::ChunkOverrun {
+                chunk,
+                offset,
+                size,
+                available,
+            } => write!(
+                f,
+                "{:?} chunk at byte {} claims {} bytes but only {} are left",
+                chunk_name(chunk),
+                offset,
+                size,
+                available
+            ),
+            WavError::Truncated { chunk, offset } => write!(
+                f,
+                "{:?} chunk at byte {} ends before all of its fields",
+                chunk_name(chunk),
+                offset
+            ),
+            WavError::InvalidChunk {
+                chunk,
+                offset,
+                reason,
+            } => write!(
+                f,
+                "{:?} chunk at byte {} is invalid: {}",
+                chunk_name(chunk),
+                offset,
+                reason
+            ),
+            WavError::MissingChunk(chunk) => write!(f, "No {:?} chunk", chunk_name(chunk)),
+            WavError::UnknownFormat { offset, tag } => write!(
+                f,
+                "unknown format tag {:#06x} in fmt chunk at byte {}",
+                tag, offset
+            ),
+            WavError::UnsupportedFormat {
+                format,
+                bits_per_sample,
+            } => write!(
+                f,
+                "unsupported sample format {:?} with {} bits per sample",
+                format, bits_per_sample
+            ),
+            WavError::UnsupportedSubFormat(guid) => {
+                write!(f, "unsupported extensible sub-format {:?}", guid)
+            }
+            WavError::InvalidBlockAlign {
+                block_align,
+                channels,
+            } => write!(
+                f,
+                "block align {} does not fit {} channels",
+                block_align, channels
+            ),
+            WavError::InvalidChannel { channel, channels } => write!(
+                f,
+                "channel {} out of range for {} channels",
+                channel, channels
+            ),
+            WavError::SeekOutOfRange { frame, frame_count } => {
+                write!(f, "cannot seek to frame {} of {}", frame, frame_count)
+            }
+            WavError::TooLarge { size } => {
+                write!(f, "{} bytes do not fit a 32 bit RIFF size", size)
+            }
+            WavError::SpecOverflow {
+                channels,
+                sample_rate,
+                bits_per_sample,
+            } => write!(
+                f,
+                "{} channels of {} bit samples at {} Hz overflow the fmt chunk",
+                channels, bits_per_sample, sample_rate
+            ),
+        }
+    }
+}
+
+impl Error for WavError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WavError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WavError {
+    fn from(error: io::Error) -> Self {
+        WavError::Io(error)
+    }
+}
+
+impl ChunkError for WavError {
+    fn truncated(chunk: [u8; 4], offset: u64) -> Self {
+        WavError::Truncated { chunk, offset }
+    }
+    fn overrun(chunk: [u8; 4], offset: u64, size: u64, available: u64) -> Self {
+        WavError::ChunkOverrun {
+            chunk,
+            offset,
+            size,
+            available,
+        }
+    }
+}
+
+impl WavError {
+    // reads of in-memory chunk bodies can only fail by running out of bytes
+    pub(super) fn truncated_at(self, chunk: [u8; 4], offset: u64) -> Self {
+        match self {
+            WavError::Io(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                WavError::Truncated { chunk, offset }
+            }
+            error => error,
+        }
+    }
+}
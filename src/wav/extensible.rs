@@ -0,0 +1,191 @@
+use std::{fmt, io::Write};
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+
+use super::{WavError, WavFormat};
+
+// every KSDATAFORMAT_SUBTYPE_* GUID for a classic format tag shares these trailing bytes
+const BASE_GUID_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+pub(super) const EXTENSIBLE_SIZE: u16 = 22;
+
+/// A sub-format GUID in its on-disk (mixed endian) byte order.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    pub const fn from_format_tag(tag: u16) -> Self {
+        let tag = tag.to_le_bytes();
+        let mut bytes = [0u8; 16];
+        bytes[0] = tag[0];
+        bytes[1] = tag[1];
+        let mut i = 0;
+        while i < BASE_GUID_TAIL.len() {
+            bytes[i + 2] = BASE_GUID_TAIL[i];
+            i += 1;
+        }
+        Self(bytes)
+    }
+
+    /// The classic format tag this GUID stands for, if it is one of the standard subtypes.
+    pub fn format_tag(&self) -> Option<u16> {
+        if self.0[2..] == BASE_GUID_TAIL {
+            Some(LittleEndian::read_u16(&self.0[0..2]))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            LittleEndian::read_u32(&b[0..4]),
+            LittleEndian::read_u16(&b[4..6]),
+            LittleEndian::read_u16(&b[6..8]),
+            b[8],
+            b[9]
+        )?;
+        b[10..]
+            .iter()
+            .try_for_each(|byte| write!(f, "{:02X}", byte))
+    }
+}
+
+pub const KSDATAFORMAT_SUBTYPE_PCM: Guid = Guid::from_format_tag(WavFormat::PCM as u16);
+pub const KSDATAFORMAT_SUBTYPE_IEEE_FLOAT: Guid = Guid::from_format_tag(WavFormat::Float as u16);
+pub const KSDATAFORMAT_SUBTYPE_ALAW: Guid = Guid::from_format_tag(WavFormat::ALaw as u16);
+pub const KSDATAFORMAT_SUBTYPE_MULAW: Guid = Guid::from_format_tag(WavFormat::MuLaw as u16);
+
+/// Speaker positions in the order their bits appear in a channel mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speaker {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    FrontLeftOfCenter,
+    FrontRightOfCenter,
+    BackCenter,
+    SideLeft,
+    SideRight,
+    TopCenter,
+    TopFrontLeft,
+    TopFrontCenter,
+    TopFrontRight,
+    TopBackLeft,
+    TopBackCenter,
+    TopBackRight,
+}
+
+const SPEAKERS: [Speaker; 18] = [
+    Speaker::FrontLeft,
+    Speaker::FrontRight,
+    Speaker::FrontCenter,
+    Speaker::LowFrequency,
+    Speaker::BackLeft,
+    Speaker::BackRight,
+    Speaker::FrontLeftOfCenter,
+    Speaker::FrontRightOfCenter,
+    Speaker::BackCenter,
+    Speaker::SideLeft,
+    Speaker::SideRight,
+    Speaker::TopCenter,
+    Speaker::TopFrontLeft,
+    Speaker::TopFrontCenter,
+    Speaker::TopFrontRight,
+    Speaker::TopBackLeft,
+    Speaker::TopBackCenter,
+    Speaker::TopBackRight,
+];
+
+impl Speaker {
+    pub fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChannelMask(pub u32);
+
+impl ChannelMask {
+    /// Speakers present in the mask, in the order their channels are interleaved.
+    pub fn speakers(&self) -> Box<[Speaker]> {
+        SPEAKERS
+            .iter()
+            .filter(|speaker| self.0 & speaker.mask() != 0)
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct FmtExtensible {
+    pub(super) valid_bits_per_sample: u16,
+    pub(super) channel_mask: ChannelMask,
+    pub(super) sub_format: Guid,
+}
+
+impl FmtExtensible {
+    /// `bytes` must hold at least `EXTENSIBLE_SIZE` bytes.
+    pub(super) fn parse<E: ByteOrder>(bytes: &[u8]) -> Self {
+        let mut sub_format = [0u8; 16];
+        sub_format.copy_from_slice(&bytes[6..22]);
+        Self {
+            valid_bits_per_sample: E::read_u16(&bytes[0..2]),
+            channel_mask: ChannelMask(E::read_u32(&bytes[2..6])),
+            sub_format: Guid(sub_format),
+        }
+    }
+
+    pub(super) fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        out.write_u16::<E>(self.valid_bits_per_sample)?;
+        out.write_u32::<E>(self.channel_mask.0)?;
+        out.write_all(&self.sub_format.0)?;
+        Ok(())
+    }
+
+    /// The format the samples are actually stored in.
+    pub(super) fn sample_format(&self) -> Result<WavFormat, WavError> {
+        self.sub_format
+            .format_tag()
+            .and_then(WavFormat::from_u16)
+            .filter(|format| *format != WavFormat::Extensible)
+            .ok_or(WavError::UnsupportedSubFormat(self.sub_format))
+    }
+}
+
+#[test]
+fn guids() {
+    assert_eq!(
+        format!("{:?}", KSDATAFORMAT_SUBTYPE_IEEE_FLOAT),
+        "00000003-0000-0010-8000-00AA00389B71"
+    );
+    assert_eq!(KSDATAFORMAT_SUBTYPE_PCM.format_tag(), Some(1));
+    assert_eq!(Guid([0xFF; 16]).format_tag(), None);
+}
+
+#[test]
+fn channel_layout() {
+    // 5.1 (back)
+    let mask = ChannelMask(0x3F);
+    assert_eq!(
+        &mask.speakers()[..],
+        &[
+            Speaker::FrontLeft,
+            Speaker::FrontRight,
+            Speaker::FrontCenter,
+            Speaker::LowFrequency,
+            Speaker::BackLeft,
+            Speaker::BackRight
+        ]
+    );
+    assert_eq!(Speaker::SideRight.mask(), 0x400);
+}
@@ -0,0 +1,298 @@
+use std::io::{Read, Write};
+
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
+
+use super::{
+    write_padded_chunk, ChunkHeader, CuePoint, Sampler, WavError, BEXT_HEADER, INFO_HEADER,
+    LIST_HEADER,
+};
+
+pub const INFO_NAME: [u8; 4] = *b"INAM";
+pub const INFO_COMMENT: [u8; 4] = *b"ICMT";
+pub const INFO_SOFTWARE: [u8; 4] = *b"ISFT";
+pub const INFO_ARTIST: [u8; 4] = *b"IART";
+pub const INFO_CREATION_DATE: [u8; 4] = *b"ICRD";
+pub const INFO_GENRE: [u8; 4] = *b"IGNR";
+pub const INFO_KEYWORDS: [u8; 4] = *b"IKEY";
+pub const INFO_SUBJECT: [u8; 4] = *b"ISBJ";
+pub const INFO_COPYRIGHT: [u8; 4] = *b"ICOP";
+pub const INFO_ENGINEER: [u8; 4] = *b"IENG";
+pub const INFO_PRODUCT: [u8; 4] = *b"IPRD";
+
+// everything in a bext chunk before the coding history
+const BEXT_FIXED_SIZE: u64 = 602;
+
+/// Descriptive tags and sampler settings kept alongside the fmt, fact and PEAK chunks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub info: InfoList,
+    pub bext: Option<Bext>,
+    pub sampler: Option<Sampler>,
+    pub cue_points: Vec<CuePoint>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.info.is_empty()
+            && self.bext.is_none()
+            && self.sampler.is_none()
+            && self.cue_points.is_empty()
+    }
+
+    pub(super) fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        if let Some(bext) = &self.bext {
+            bext.write::<E, _>(out)?;
+        }
+        if !self.info.is_empty() {
+            self.info.write::<E, _>(out)?;
+        }
+        if let Some(sampler) = &self.sampler {
+            sampler.write::<E, _>(out)?;
+        }
+        if !self.cue_points.is_empty() {
+            CuePoint::write_list::<E, _>(&self.cue_points, out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Text tags from a LIST/INFO chunk, in file order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InfoList {
+    tags: Vec<([u8; 4], String)>,
+}
+
+impl InfoList {
+    pub fn get(&self, id: [u8; 4]) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_id, _)| *tag_id == id)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the tag if it is already present, otherwise appends it.
+    pub fn set(&mut self, id: [u8; 4], value: impl Into<String>) {
+        let value = value.into();
+        match self.tags.iter_mut().find(|(tag_id, _)| *tag_id == id) {
+            Some((_, old)) => *old = value,
+            None => self.tags.push((id, value)),
+        }
+    }
+
+    pub fn remove(&mut self, id: [u8; 4]) -> Option<String> {
+        let index = self.tags.iter().position(|(tag_id, _)| *tag_id == id)?;
+        Some(self.tags.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ([u8; 4], &str)> {
+        self.tags.iter().map(|(id, value)| (*id, value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    // `data` is the body of a LIST chunk whose list type is INFO
+    pub(super) fn read<E: ByteOrder, T: Read>(
+        data: &mut T,
+        header: &ChunkHeader,
+    ) -> Result<Self, WavError> {
+        let mut list_type = [0u8; 4];
+        data.read_exact(&mut list_type)?;
+        let mut remaining = header.size.saturating_sub(4);
+        let mut tags = vec![];
+        while remaining >= 8 {
+            let mut id = [0u8; 4];
+            data.read_exact(&mut id)?;
+            let size = data.read_u32::<E>()? as u64;
+            remaining -= 8;
+            if size > remaining {
+                return Err(WavError::Truncated {
+                    chunk: header.id,
+                    offset: header.offset(),
+                });
+            }
+            let mut text = vec![0; size as usize];
+            data.read_exact(&mut text)?;
+            remaining -= size;
+            // the pad byte may be missing on the last tag
+            if size % 2 == 1 && remaining > 0 {
+                data.read_u8()?;
+                remaining -= 1;
+            }
+            tags.push((id, read_text(&text)));
+        }
+        Ok(Self { tags })
+    }
+
+    fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        let mut body = INFO_HEADER.to_vec();
+        for (id, value) in self.tags.iter() {
+            // stored NUL terminated
+            let mut text = value.as_bytes().to_vec();
+            text.push(0);
+            write_padded_chunk::<E, _>(&mut body, *id, &text)?;
+        }
+        write_padded_chunk::<E, _>(out, LIST_HEADER, &body)
+    }
+}
+
+/// The Broadcast Wave (EBU Tech 3285) `bext` chunk.
+///
+/// Loudness fields are in hundredths of a LU/LUFS/dBTP, as stored on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bext {
+    pub description: String,
+    pub originator: String,
+    pub originator_reference: String,
+    /// yyyy-mm-dd
+    pub origination_date: String,
+    /// hh:mm:ss
+    pub origination_time: String,
+    /// first sample of the file counted in samples since midnight
+    pub time_reference: u64,
+    pub version: u16,
+    pub umid: [u8; 64],
+    pub loudness_value: i16,
+    pub loudness_range: i16,
+    pub max_true_peak_level: i16,
+    pub max_momentary_loudness: i16,
+    pub max_short_term_loudness: i16,
+    pub coding_history: String,
+}
+
+impl Default for Bext {
+    fn default() -> Self {
+        Self {
+            description: String::new(),
+            originator: String::new(),
+            originator_reference: String::new(),
+            origination_date: String::new(),
+            origination_time: String::new(),
+            time_reference: 0,
+            version: 2,
+            umid: [0; 64],
+            loudness_value: 0,
+            loudness_range: 0,
+            max_true_peak_level: 0,
+            max_momentary_loudness: 0,
+            max_short_term_loudness: 0,
+            coding_history: String::new(),
+        }
+    }
+}
+
+impl Bext {
+    pub(super) fn read<E: ByteOrder, T: Read>(
+        data: &mut T,
+        header: &ChunkHeader,
+    ) -> Result<Self, WavError> {
+        let mut umid = [0u8; 64];
+        let description = read_fixed_text(data, 256)?;
+        let originator = read_fixed_text(data, 32)?;
+        let originator_reference = read_fixed_text(data, 32)?;
+        let origination_date = read_fixed_text(data, 10)?;
+        let origination_time = read_fixed_text(data, 8)?;
+        let time_reference_low = data.read_u32::<E>()? as u64;
+        let time_reference_high = data.read_u32::<E>()? as u64;
+        let version = data.read_u16::<E>()?;
+        data.read_exact(&mut umid)?;
+        let loudness_value = data.read_i16::<E>()?;
+        let loudness_range = data.read_i16::<E>()?;
+        let max_true_peak_level = data.read_i16::<E>()?;
+        let max_momentary_loudness = data.read_i16::<E>()?;
+        let max_short_term_loudness = data.read_i16::<E>()?;
+        data.read_exact(&mut [0u8; 180])?;
+        let mut coding_history = vec![];
+        data.take(header.size.saturating_sub(BEXT_FIXED_SIZE))
+            .read_to_end(&mut coding_history)?;
+        Ok(Self {
+            description,
+            originator,
+            originator_reference,
+            origination_date,
+            origination_time,
+            time_reference: time_reference_high << 32 | time_reference_low,
+            version,
+            umid,
+            loudness_value,
+            loudness_range,
+            max_true_peak_level,
+            max_momentary_loudness,
+            max_short_term_loudness,
+            coding_history: read_text(&coding_history),
+        })
+    }
+
+    fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        let mut body = vec![];
+        write_fixed_text(&mut body, &self.description, 256);
+        write_fixed_text(&mut body, &self.originator, 32);
+        write_fixed_text(&mut body, &self.originator_reference, 32);
+        write_fixed_text(&mut body, &self.origination_date, 10);
+        write_fixed_text(&mut body, &self.origination_time, 8);
+        body.write_u32::<E>(self.time_reference as u32)?;
+        body.write_u32::<E>((self.time_reference >> 32) as u32)?;
+        body.write_u16::<E>(self.version)?;
+        body.write_all(&self.umid)?;
+        body.write_i16::<E>(self.loudness_value)?;
+        body.write_i16::<E>(self.loudness_range)?;
+        body.write_i16::<E>(self.max_true_peak_level)?;
+        body.write_i16::<E>(self.max_momentary_loudness)?;
+        body.write_i16::<E>(self.max_short_term_loudness)?;
+        body.write_all(&[0; 180])?;
+        body.write_all(self.coding_history.as_bytes())?;
+        write_padded_chunk::<E, _>(out, BEXT_HEADER, &body)
+    }
+}
+
+// text fields end at the first NUL; anything that isn't UTF-8 is replaced
+fn read_text(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn read_fixed_text<T: Read>(data: &mut T, len: usize) -> Result<String, WavError> {
+    let mut bytes = vec![0; len];
+    data.read_exact(&mut bytes)?;
+    Ok(read_text(&bytes))
+}
+
+// NUL padded, cut at the last whole character that fits
+fn write_fixed_text(body: &mut Vec<u8>, text: &str, len: usize) {
+    let mut end = text.len().min(len);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    body.extend_from_slice(&text.as_bytes()[..end]);
+    body.resize(body.len() + len - end, 0);
+}
+
+#[test]
+fn info_round_trip() {
+    use byteorder::LittleEndian;
+
+    let mut info = InfoList::default();
+    info.set(INFO_NAME, "Low E take 3");
+    info.set(INFO_COMMENT, "drop D");
+    info.set(INFO_NAME, "Low D take 3");
+    let mut bytes = vec![];
+    info.write::<LittleEndian, _>(&mut bytes).unwrap();
+    assert_eq!(&bytes[..12], b"LIST\x2A\0\0\0INFO");
+    assert_eq!(&bytes[12..20], b"INAM\x0D\0\0\0");
+
+    let header = ChunkHeader {
+        id: LIST_HEADER,
+        size: bytes.len() as u64 - 8,
+        start: 8,
+        bytes_left: bytes.len() as u64 - 8,
+    };
+    let read = InfoList::read::<LittleEndian, _>(&mut &bytes[8..], &header).unwrap();
+    assert_eq!(read, info);
+    assert_eq!(read.get(INFO_NAME), Some("Low D take 3"));
+    assert_eq!(read.get(INFO_SOFTWARE), None);
+}
@@ -0,0 +1,284 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::chunk::bytes_remaining;
+
+use super::{
+    decode_block, read_chunk_header, read_container, read_list, read_riff_header, skip_chunk, Bext,
+    ChannelSelection, Container, CuePoint, FmtChunk, Metadata, RiffChunk, Sampler, WavError,
+    WavSpec, BEXT_HEADER, CUE_HEADER, DATA_HEADER, FMT_HEADER, LIST_HEADER, PEAK_HEADER,
+    SMPL_HEADER,
+};
+
+/// Reads a WAVE file's headers up front and decodes the data chunk on demand,
+/// so only the requested block of samples is ever held in memory.
+pub struct WavReader<R: Read + Seek> {
+    inner: R,
+    container: Container,
+    fmt_chunk: FmtChunk,
+    has_peak_chunk: bool,
+    metadata: Metadata,
+    data_start: u64,
+    frame_count: u64,
+    position: u64, // in frames
+    buffer: Vec<u8>,
+}
+
+impl<R: Read + Seek> WavReader<R> {
+    pub fn new(mut inner: R) -> Result<Self, WavError> {
+        let container = read_container(&mut inner)?;
+        match container {
+            Container::Rifx => Self::scan::<BigEndian>(inner, container),
+            _ => Self::scan::<LittleEndian>(inner, container),
+        }
+    }
+
+    fn scan<E: ByteOrder>(mut inner: R, container: Container) -> Result<Self, WavError> {
+        let header = read_riff_header::<E, _>(&mut inner, container)?;
+
+        let mut fmt_chunk: Option<FmtChunk> = None;
+        let mut data: Option<(u64, u64)> = None;
+        let mut has_peak_chunk = false;
+        let mut metadata = Metadata::default();
+        while bytes_remaining::<WavError, _>(&mut inner)? > 0 {
+            let chunk = read_chunk_header::<E, _>(&mut inner, header.ds64.as_ref())?;
+            match chunk.id {
+                FMT_HEADER => {
+                    fmt_chunk = Some(chunk.read_body(&mut inner, FmtChunk::read::<E, _>)?)
+                }
+                DATA_HEADER => data = Some((chunk.start, chunk.size)),
+                PEAK_HEADER => has_peak_chunk = true,
+                BEXT_HEADER => {
+                    metadata.bext = Some(chunk.read_body(&mut inner, Bext::read::<E, _>)?)
+                }
+                SMPL_HEADER => {
+                    metadata.sampler = Some(chunk.read_body(&mut inner, Sampler::read::<E, _>)?)
+                }
+                CUE_HEADER => {
+                    metadata.cue_points =
+                        chunk.read_body(&mut inner, CuePoint::read_list::<E, _>)?
+                }
+                LIST_HEADER => {
+                    if let RiffChunk::Info(info) = read_list::<E, _>(&mut inner, &chunk)? {
+                        info.iter()
+                            .for_each(|(id, value)| metadata.info.set(id, value));
+                    }
+                }
+                _ => {}
+            }
+            skip_chunk(&mut inner, &chunk)?;
+        }
+        let fmt_chunk = fmt_chunk.ok_or(WavError::MissingChunk(FMT_HEADER))?;
+        let (data_start, data_size) = data.ok_or(WavError::MissingChunk(DATA_HEADER))?;
+
+        // fail on unsupported formats now rather than on the first read
+        decode_block(container, &fmt_chunk, &[])?;
+
+        inner.seek(SeekFrom::Start(data_start))?;
+        Ok(Self {
+            inner,
+            container,
+            frame_count: data_size / fmt_chunk.block_align as u64,
+            fmt_chunk,
+            has_peak_chunk,
+            metadata,
+            data_start,
+            position: 0,
+            buffer: vec![],
+        })
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        WavSpec {
+            format: self.fmt_chunk.format,
+            channels: self.fmt_chunk.channels,
+            sample_rate: self.fmt_chunk.sample_rate,
+            bits_per_sample: self.fmt_chunk.bits_per_sample,
+            peak_chunk: self.has_peak_chunk,
+        }
+    }
+
+    /// Tags, sampler settings and cue points, wherever they appear in the file.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn container(&self) -> Container {
+        self.container
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.fmt_chunk.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.fmt_chunk.channels
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Index of the next frame `read_frames` will return.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Moves to the given frame; seeking to `frame_count()` leaves the reader at the end.
+    pub fn seek(&mut self, frame: u64) -> Result<(), WavError> {
+        if frame > self.frame_count {
+            return Err(WavError::SeekOutOfRange {
+                frame,
+                frame_count: self.frame_count,
+            });
+        }
+        let offset = frame * self.fmt_chunk.block_align as u64;
+        self.inner.seek(SeekFrom::Start(self.data_start + offset))?;
+        self.position = frame;
+        Ok(())
+    }
+
+    /// Decodes up to `frames` interleaved frames; an empty result means the data chunk is exhausted.
+    pub fn read_frames(&mut self, frames: usize) -> Result<Box<[f32]>, WavError> {
+        let frames = (frames as u64).min(self.frame_count - self.position);
+        let len = frames as usize * self.fmt_chunk.block_align as usize;
+        self.buffer.resize(len, 0);
+        self.inner.read_exact(&mut self.buffer)?;
+        self.position += frames;
+        decode_block(self.container, &self.fmt_chunk, &self.buffer)
+    }
+
+    /// Like `read_frames`, but reduced to the single signal picked by `selection`.
+    pub fn read_mono_frames(
+        &mut self,
+        frames: usize,
+        selection: ChannelSelection,
+    ) -> Result<Box<[f32]>, WavError> {
+        selection.apply(&self.read_frames(frames)?, self.fmt_chunk.channels)
+    }
+
+    /// Iterates over the rest of the data chunk `frames` at a time.
+    pub fn blocks(&mut self, frames: usize) -> Blocks<'_, R> {
+        Blocks {
+            reader: self,
+            frames,
+            selection: None,
+        }
+    }
+
+    /// Iterates like `blocks`, yielding only the signal picked by `selection`.
+    pub fn mono_blocks(&mut self, frames: usize, selection: ChannelSelection) -> Blocks<'_, R> {
+        Blocks {
+            reader: self,
+            frames,
+            selection: Some(selection),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+pub struct Blocks<'a, R: Read + Seek> {
+    reader: &'a mut WavReader<R>,
+    frames: usize,
+    selection: Option<ChannelSelection>,
+}
+
+impl<R: Read + Seek> Iterator for Blocks<'_, R> {
+    type Item = Result<Box<[f32]>, WavError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frames == 0 {
+            return None;
+        }
+        let block = match self.selection {
+            Some(selection) => self.reader.read_mono_frames(self.frames, selection),
+            None => self.reader.read_frames(self.frames),
+        };
+        match block {
+            Ok(block) if block.is_empty() => None,
+            result => Some(result),
+        }
+    }
+}
+
+#[test]
+fn streams_like_from_bytes() {
+    use super::WavFile;
+    use crate::audio_analysis::{AudioAnalyzer, Note, WindowType};
+    use std::io::Cursor;
+
+    let file = include_bytes!("../.././A_RECORDING.wav");
+    let wav = WavFile::from_bytes(&mut Cursor::new(file)).unwrap();
+    let mut reader = WavReader::new(Cursor::new(file)).unwrap();
+    assert_eq!(reader.spec(), wav.spec());
+    assert_eq!(reader.frame_count(), wav.frame_count());
+
+    let mut analyzer = AudioAnalyzer::new(48000, 1024 * 50, 3, 3, 440, WindowType::Hann);
+    let mut streamed = vec![];
+    for block in reader.blocks(1024) {
+        let block = block.unwrap();
+        analyzer.add_samples(&block);
+        streamed.extend_from_slice(&block);
+    }
+    assert_eq!(&streamed[..], &wav.get_samples().unwrap()[..]);
+    assert_eq!(Note::from_frequency(analyzer.strongest_freq()), Note::A);
+}
+
+#[test]
+fn seeking() {
+    use super::WavFile;
+    use std::io::Cursor;
+
+    let file = include_bytes!("../.././B.wav");
+    let samples = WavFile::from_bytes(&mut Cursor::new(file))
+        .unwrap()
+        .get_samples()
+        .unwrap();
+    let mut reader = WavReader::new(Cursor::new(file)).unwrap();
+
+    reader.seek(1000).unwrap();
+    assert_eq!(&reader.read_frames(16).unwrap()[..], &samples[1000..1016]);
+    assert_eq!(reader.position(), 1016);
+
+    let end = reader.frame_count();
+    reader.seek(end - 4).unwrap();
+    assert_eq!(reader.read_frames(16).unwrap().len(), 4);
+    assert!(reader.read_frames(16).unwrap().is_empty());
+    assert!(reader.seek(end + 1).is_err());
+}
+
+#[test]
+fn selecting_a_channel() {
+    use super::{WavFormat, WavWriter};
+    use crate::audio_analysis::{AudioAnalyzer, Note, WindowType};
+    use std::{f32::consts::PI, io::Cursor};
+
+    // A4 on the left, B4 on the right
+    let spec = WavSpec {
+        format: WavFormat::Float,
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        peak_chunk: false,
+    };
+    let mut writer = WavWriter::new(Cursor::new(vec![]), spec).unwrap();
+    for i in 0..48000 {
+        let t = i as f32 / 48000.0;
+        writer.write_sample((2.0 * PI * 440.0 * t).sin()).unwrap();
+        writer.write_sample((2.0 * PI * 493.88 * t).sin()).unwrap();
+    }
+    let cursor = writer.finalize().unwrap();
+
+    for (channel, note) in [(0, Note::A), (1, Note::B)] {
+        let mut reader = WavReader::new(Cursor::new(cursor.get_ref())).unwrap();
+        let mut analyzer = AudioAnalyzer::new(48000, 1024 * 32, 0, 3, 440, WindowType::Hann);
+        for block in reader.mono_blocks(1024, ChannelSelection::Channel(channel)) {
+            analyzer.add_samples(&block.unwrap());
+        }
+        assert_eq!(Note::from_frequency(analyzer.strongest_freq()), note);
+    }
+}
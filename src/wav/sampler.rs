@@ -0,0 +1,231 @@
+use std::io::{Read, Write};
+
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
+
+use super::{write_padded_chunk, ChunkHeader, WavError, CUE_HEADER, SMPL_HEADER};
+
+// smpl fields before the loop list, and the size of each loop and cue point
+const SAMPLER_FIXED_SIZE: u64 = 36;
+const LOOP_SIZE: u64 = 24;
+const CUE_POINT_SIZE: u64 = 24;
+
+/// The `smpl` chunk sampler instruments use to map a sample onto the keyboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sampler {
+    pub manufacturer: u32,
+    pub product: u32,
+    /// length of one sample frame in nanoseconds
+    pub sample_period: u32,
+    /// MIDI note the sample plays back at its recorded pitch
+    pub midi_unity_note: u32,
+    /// how far above the unity note the recorded pitch is, in 1/2^32 of a semitone
+    pub midi_pitch_fraction: u32,
+    pub smpte_format: u32,
+    pub smpte_offset: u32,
+    pub loops: Vec<SampleLoop>,
+    pub sampler_data: Box<[u8]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SampleLoop {
+    pub cue_point_id: u32,
+    /// 0 forward, 1 alternating, 2 backward
+    pub loop_type: u32,
+    /// first and last frame of the loop, both inclusive
+    pub start: u32,
+    pub end: u32,
+    pub fraction: u32,
+    /// 0 loops forever
+    pub play_count: u32,
+}
+
+/// A marker from the `cue ` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CuePoint {
+    pub id: u32,
+    pub position: u32,
+    pub data_chunk_id: [u8; 4],
+    pub chunk_start: u32,
+    pub block_start: u32,
+    /// frame the cue points at within the data chunk
+    pub sample_offset: u32,
+}
+
+impl Sampler {
+    /// An empty chunk with its root note set to `note`, for files that don't have one yet.
+    pub fn new(sample_rate: u32, note: f32) -> Self {
+        let mut sampler = Self {
+            manufacturer: 0,
+            product: 0,
+            sample_period: match sample_rate {
+                0 => 0,
+                sample_rate => (1_000_000_000 / sample_rate as u64) as u32,
+            },
+            midi_unity_note: 0,
+            midi_pitch_fraction: 0,
+            smpte_format: 0,
+            smpte_offset: 0,
+            loops: vec![],
+            sampler_data: Box::default(),
+        };
+        sampler.set_root_note(note);
+        sampler
+    }
+
+    /// The recorded pitch as a fractional MIDI note number.
+    pub fn root_note(&self) -> f32 {
+        (self.midi_unity_note as f64 + self.midi_pitch_fraction as f64 / 4294967296.0) as f32
+    }
+
+    /// Splits a fractional MIDI note number into unity note and pitch fraction.
+    pub fn set_root_note(&mut self, note: f32) {
+        let note = (note as f64).clamp(0.0, 127.0);
+        let unity_note = note.floor();
+        self.midi_unity_note = unity_note as u32;
+        self.midi_pitch_fraction = ((note - unity_note) * 4294967296.0).round() as u32;
+    }
+
+    pub(super) fn read<E: ByteOrder, T: Read>(
+        data: &mut T,
+        header: &ChunkHeader,
+    ) -> Result<Self, WavError> {
+        let manufacturer = data.read_u32::<E>()?;
+        let product = data.read_u32::<E>()?;
+        let sample_period = data.read_u32::<E>()?;
+        let midi_unity_note = data.read_u32::<E>()?;
+        let midi_pitch_fraction = data.read_u32::<E>()?;
+        let smpte_format = data.read_u32::<E>()?;
+        let smpte_offset = data.read_u32::<E>()?;
+        let loop_count = data.read_u32::<E>()?;
+        let sampler_data_size = data.read_u32::<E>()?;
+        if loop_count as u64 * LOOP_SIZE + sampler_data_size as u64
+            > header.size.saturating_sub(SAMPLER_FIXED_SIZE)
+        {
+            return Err(WavError::Truncated {
+                chunk: header.id,
+                offset: header.offset(),
+            });
+        }
+        let loops = (0..loop_count)
+            .map(|_| {
+                Ok(SampleLoop {
+                    cue_point_id: data.read_u32::<E>()?,
+                    loop_type: data.read_u32::<E>()?,
+                    start: data.read_u32::<E>()?,
+                    end: data.read_u32::<E>()?,
+                    fraction: data.read_u32::<E>()?,
+                    play_count: data.read_u32::<E>()?,
+                })
+            })
+            .collect::<Result<Vec<_>, WavError>>()?;
+        let mut sampler_data = vec![0; sampler_data_size as usize];
+        data.read_exact(&mut sampler_data)?;
+        Ok(Self {
+            manufacturer,
+            product,
+            sample_period,
+            midi_unity_note,
+            midi_pitch_fraction,
+            smpte_format,
+            smpte_offset,
+            loops,
+            sampler_data: sampler_data.into_boxed_slice(),
+        })
+    }
+
+    pub(super) fn write<E: ByteOrder, W: Write>(&self, out: &mut W) -> Result<(), WavError> {
+        let mut body = vec![];
+        body.write_u32::<E>(self.manufacturer)?;
+        body.write_u32::<E>(self.product)?;
+        body.write_u32::<E>(self.sample_period)?;
+        body.write_u32::<E>(self.midi_unity_note)?;
+        body.write_u32::<E>(self.midi_pitch_fraction)?;
+        body.write_u32::<E>(self.smpte_format)?;
+        body.write_u32::<E>(self.smpte_offset)?;
+        body.write_u32::<E>(self.loops.len() as u32)?;
+        body.write_u32::<E>(self.sampler_data.len() as u32)?;
+        for sample_loop in self.loops.iter() {
+            body.write_u32::<E>(sample_loop.cue_point_id)?;
+            body.write_u32::<E>(sample_loop.loop_type)?;
+            body.write_u32::<E>(sample_loop.start)?;
+            body.write_u32::<E>(sample_loop.end)?;
+            body.write_u32::<E>(sample_loop.fraction)?;
+            body.write_u32::<E>(sample_loop.play_count)?;
+        }
+        body.write_all(&self.sampler_data)?;
+        write_padded_chunk::<E, _>(out, SMPL_HEADER, &body)
+    }
+}
+
+impl CuePoint {
+    pub(super) fn read_list<E: ByteOrder, T: Read>(
+        data: &mut T,
+        header: &ChunkHeader,
+    ) -> Result<Vec<Self>, WavError> {
+        let count = data.read_u32::<E>()?;
+        if count as u64 * CUE_POINT_SIZE > header.size.saturating_sub(4) {
+            return Err(WavError::Truncated {
+                chunk: header.id,
+                offset: header.offset(),
+            });
+        }
+        (0..count)
+            .map(|_| {
+                let id = data.read_u32::<E>()?;
+                let position = data.read_u32::<E>()?;
+                let mut data_chunk_id = [0u8; 4];
+                data.read_exact(&mut data_chunk_id)?;
+                Ok(Self {
+                    id,
+                    position,
+                    data_chunk_id,
+                    chunk_start: data.read_u32::<E>()?,
+                    block_start: data.read_u32::<E>()?,
+                    sample_offset: data.read_u32::<E>()?,
+                })
+            })
+            .collect()
+    }
+
+    pub(super) fn write_list<E: ByteOrder, W: Write>(
+        cue_points: &[Self],
+        out: &mut W,
+    ) -> Result<(), WavError> {
+        let mut body = vec![];
+        body.write_u32::<E>(cue_points.len() as u32)?;
+        for cue in cue_points {
+            body.write_u32::<E>(cue.id)?;
+            body.write_u32::<E>(cue.position)?;
+            body.write_all(&cue.data_chunk_id)?;
+            body.write_u32::<E>(cue.chunk_start)?;
+            body.write_u32::<E>(cue.block_start)?;
+            body.write_u32::<E>(cue.sample_offset)?;
+        }
+        write_padded_chunk::<E, _>(out, CUE_HEADER, &body)
+    }
+}
+
+#[test]
+fn root_note() {
+    let mut sampler = Sampler::new(48000, 69.0);
+    assert_eq!(sampler.sample_period, 20833);
+    assert_eq!(
+        (sampler.midi_unity_note, sampler.midi_pitch_fraction),
+        (69, 0)
+    );
+
+    // 50 cents above middle C
+    sampler.set_root_note(60.5);
+    assert_eq!(
+        (sampler.midi_unity_note, sampler.midi_pitch_fraction),
+        (60, 0x80000000)
+    );
+    assert_eq!(sampler.root_note(), 60.5);
+
+    // the pitch fraction only goes up, so flat samples use the note below
+    sampler.set_root_note(63.75);
+    assert_eq!(
+        (sampler.midi_unity_note, sampler.midi_pitch_fraction),
+        (63, 0xC0000000)
+    );
+}
@@ -0,0 +1,348 @@
+use std::{
+    io::{Seek, SeekFrom, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use super::{
+    FactChunk, FmtChunk, Metadata, PeakChunk, PositionPeak, WavError, WavFormat, DATA_HEADER,
+    FACT_HEADER, FMT_HEADER, PEAK_HEADER, RIFF_HEADER, WAVE_HEADER,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavSpec {
+    pub format: WavFormat,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    pub peak_chunk: bool,
+}
+
+impl WavSpec {
+    /// Checks the spec can be written, returning its block align and byte rate.
+    fn validate(&self) -> Result<(u16, u32), WavError> {
+        if self.channels == 0 {
+            return Err(WavError::InvalidChannel {
+                channel: 0,
+                channels: 0,
+            });
+        }
+        match (self.format, self.bits_per_sample) {
+            (WavFormat::PCM, 8 | 16 | 24 | 32) | (WavFormat::Float, 32 | 64) => {}
+            (format, bits_per_sample) => {
+                return Err(WavError::UnsupportedFormat {
+                    format,
+                    bits_per_sample,
+                })
+            }
+        }
+        self.channels
+            .checked_mul(self.bits_per_sample / 8)
+            .and_then(|block_align| {
+                let byte_rate = self.sample_rate.checked_mul(block_align as u32)?;
+                Some((block_align, byte_rate))
+            })
+            .ok_or(WavError::SpecOverflow {
+                channels: self.channels,
+                sample_rate: self.sample_rate,
+                bits_per_sample: self.bits_per_sample,
+            })
+    }
+}
+
+/// Writes samples to a RIFF/WAVE stream, patching the header sizes in `finalize`.
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    spec: WavSpec,
+    start: u64,
+    fact_position: Option<u64>,
+    peak_position: Option<u64>,
+    data_size_position: u64,
+    samples_written: u64,
+    peaks: Box<[PositionPeak]>,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(out: W, spec: WavSpec) -> Result<Self, WavError> {
+        Self::with_metadata(out, spec, &Metadata::default())
+    }
+
+    /// Like `new`, with LIST/INFO and `bext` chunks written ahead of the data.
+    pub fn with_metadata(mut out: W, spec: WavSpec, metadata: &Metadata) -> Result<Self, WavError> {
+        let (block_align, byte_rate) = spec.validate()?;
+        let start = out.stream_position()?;
+        out.write_all(&RIFF_HEADER)?;
+        out.write_u32::<LittleEndian>(0)?;
+        out.write_all(&WAVE_HEADER)?;
+
+        // non-PCM formats carry an (empty) cbSize field
+        let (extra_data_size, extra_data) = match spec.format {
+            WavFormat::PCM => (None, None),
+            _ => (Some(0), Some(Box::default())),
+        };
+        FmtChunk {
+            fmt_str: FMT_HEADER,
+            format: spec.format,
+            channels: spec.channels,
+            sample_rate: spec.sample_rate,
+            byte_rate,
+            block_align,
+            bits_per_sample: spec.bits_per_sample,
+            extra_data_size,
+            extensible: None,
+            extra_data,
+        }
+        .write::<LittleEndian, _>(&mut out)?;
+
+        let fact_position = if spec.format != WavFormat::PCM {
+            let position = out.stream_position()?;
+            FactChunk {
+                fact_str: FACT_HEADER,
+                data: 0,
+            }
+            .write::<LittleEndian, _>(&mut out)?;
+            Some(position)
+        } else {
+            None
+        };
+
+        let peaks = vec![PositionPeak::default(); spec.channels as usize].into_boxed_slice();
+        let peak_position = if spec.peak_chunk {
+            let position = out.stream_position()?;
+            Self::peak_chunk(&peaks).write::<LittleEndian, _>(&mut out)?;
+            Some(position)
+        } else {
+            None
+        };
+
+        metadata.write::<LittleEndian, _>(&mut out)?;
+
+        out.write_all(&DATA_HEADER)?;
+        let data_size_position = out.stream_position()?;
+        out.write_u32::<LittleEndian>(0)?;
+
+        Ok(Self {
+            out,
+            spec,
+            start,
+            fact_position,
+            peak_position,
+            data_size_position,
+            samples_written: 0,
+            peaks,
+        })
+    }
+
+    fn peak_chunk(peaks: &[PositionPeak]) -> PeakChunk {
+        let time_stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as u32)
+            .unwrap_or(0);
+        PeakChunk {
+            peak_str: PEAK_HEADER,
+            version: 1,
+            time_stamp,
+            peaks: peaks.into(),
+        }
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// Writes one sample in `[-1.0, 1.0]`; channels are interleaved in call order.
+    pub fn write_sample(&mut self, sample: f32) -> Result<(), WavError> {
+        let channel = (self.samples_written % self.spec.channels as u64) as usize;
+        let frame = self.samples_written / self.spec.channels as u64;
+        let peak = &mut self.peaks[channel];
+        if sample.abs() > peak.value {
+            peak.value = sample.abs();
+            peak.position = frame as u32;
+        }
+
+        let out = &mut self.out;
+        match (self.spec.format, self.spec.bits_per_sample) {
+            (WavFormat::PCM, 8) => {
+                out.write_u8((quantize(sample, 8) + 128) as u8)?;
+            }
+            (WavFormat::PCM, 16) => {
+                out.write_i16::<LittleEndian>(quantize(sample, 16) as i16)?;
+            }
+            (WavFormat::PCM, 24) => {
+                out.write_i24::<LittleEndian>(quantize(sample, 24) as i32)?;
+            }
+            (WavFormat::PCM, 32) => {
+                out.write_i32::<LittleEndian>(quantize(sample, 32) as i32)?;
+            }
+            (WavFormat::Float, 32) => out.write_f32::<LittleEndian>(sample)?,
+            (WavFormat::Float, 64) => out.write_f64::<LittleEndian>(sample as f64)?,
+            _ => unreachable!("spec is validated in WavWriter::new"),
+        }
+        self.samples_written += 1;
+        Ok(())
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> Result<(), WavError> {
+        samples
+            .iter()
+            .try_for_each(|sample| self.write_sample(*sample))
+    }
+
+    /// Pads the data chunk, patches every size field and returns the inner writer.
+    pub fn finalize(mut self) -> Result<W, WavError> {
+        let data_size = self.samples_written * (self.spec.bits_per_sample / 8) as u64;
+        if data_size % 2 == 1 {
+            self.out.write_u8(0)?;
+        }
+        let end = self.out.stream_position()?;
+        let size = end - self.start - 8;
+        let riff_size = u32::try_from(size).map_err(|_| WavError::TooLarge { size })?;
+
+        self.out.seek(SeekFrom::Start(self.start + 4))?;
+        self.out.write_u32::<LittleEndian>(riff_size)?;
+        self.out.seek(SeekFrom::Start(self.data_size_position))?;
+        self.out.write_u32::<LittleEndian>(data_size as u32)?;
+
+        if let Some(position) = self.fact_position {
+            let frames = self.samples_written / self.spec.channels as u64;
+            self.out.seek(SeekFrom::Start(position))?;
+            FactChunk {
+                fact_str: FACT_HEADER,
+                data: frames as u32,
+            }
+            .write::<LittleEndian, _>(&mut self.out)?;
+        }
+        if let Some(position) = self.peak_position {
+            self.out.seek(SeekFrom::Start(position))?;
+            Self::peak_chunk(&self.peaks).write::<LittleEndian, _>(&mut self.out)?;
+        }
+
+        self.out.seek(SeekFrom::Start(end))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+// full scale is 2^(bits - 1) so that decoding divides by a power of two
+fn quantize(sample: f32, bits: u32) -> i64 {
+    let full_scale = (1i64 << (bits - 1)) as f64;
+    (sample as f64 * full_scale)
+        .round()
+        .clamp(-full_scale, full_scale - 1.0) as i64
+}
+
+#[cfg(test)]
+fn float_spec(channels: u16) -> WavSpec {
+    WavSpec {
+        format: WavFormat::Float,
+        channels,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        peak_chunk: true,
+    }
+}
+
+#[test]
+fn float_round_trip() {
+    use super::WavFile;
+    use std::io::Cursor;
+
+    let samples = (0..1000)
+        .map(|i| (i as f32 * 0.05).sin() * 0.5)
+        .collect::<Box<[f32]>>();
+    let mut writer = WavWriter::new(Cursor::new(vec![]), float_spec(2)).unwrap();
+    writer.write_samples(&samples).unwrap();
+    let mut cursor = writer.finalize().unwrap();
+    cursor.set_position(0);
+
+    let wav = WavFile::from_bytes(&mut cursor).unwrap();
+    assert_eq!(wav.spec(), float_spec(2));
+    assert_eq!(wav.get_samples().unwrap(), samples);
+    assert_eq!(wav.fact_chunk.as_ref().unwrap().data, 500);
+    let peaks = &wav.peak_chunk.as_ref().unwrap().peaks;
+    assert_eq!(peaks.len(), 2);
+    assert_eq!(
+        peaks[0].value,
+        samples.iter().step_by(2).fold(0.0, |a, b| b.abs().max(a))
+    );
+}
+
+#[test]
+fn pcm_round_trip() {
+    use super::WavFile;
+    use std::io::Cursor;
+
+    for bits in [8u16, 16, 24, 32] {
+        let spec = WavSpec {
+            format: WavFormat::PCM,
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: bits,
+            peak_chunk: false,
+        };
+        // odd sample count exercises the pad byte for 8 and 24 bit data
+        let samples = [0.0, 0.5, -0.5, 1.0, -1.0];
+        let mut writer = WavWriter::new(Cursor::new(vec![]), spec).unwrap();
+        writer.write_samples(&samples).unwrap();
+        let mut cursor = writer.finalize().unwrap();
+        assert_eq!(cursor.get_ref().len() % 2, 0);
+        cursor.set_position(0);
+
+        let wav = WavFile::from_bytes(&mut cursor).unwrap();
+        assert_eq!(wav.spec(), spec);
+        assert!(wav.fact_chunk.is_none());
+        assert_eq!(
+            &wav.get_samples().unwrap()[..],
+            &[0.0, 0.5, -0.5, 1.0 - 2.0f32.powi(1 - bits as i32), -1.0]
+        );
+    }
+}
+
+#[test]
+fn rejects_unsupported_spec() {
+    use std::io::Cursor;
+
+    let spec = WavSpec {
+        format: WavFormat::MuLaw,
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 8,
+        peak_chunk: false,
+    };
+    assert!(WavWriter::new(Cursor::new(vec![]), spec).is_err());
+}
+
+#[test]
+fn rejects_overflowing_spec() {
+    use std::io::Cursor;
+
+    let spec = WavSpec {
+        bits_per_sample: 64,
+        ..float_spec(9000)
+    };
+    assert!(matches!(
+        WavWriter::new(Cursor::new(vec![]), spec),
+        Err(WavError::SpecOverflow { channels: 9000, .. })
+    ));
+    let spec = WavSpec {
+        sample_rate: u32::MAX,
+        ..float_spec(2)
+    };
+    assert!(matches!(
+        WavWriter::new(Cursor::new(vec![]), spec),
+        Err(WavError::SpecOverflow { .. })
+    ));
+}
+
+#[test]
+fn rewrite_is_byte_identical() {
+    use super::WavFile;
+    use std::io::Cursor;
+
+    let file = include_bytes!("../.././A.wav");
+    let wav = WavFile::from_bytes(&mut Cursor::new(file)).unwrap();
+    let mut out = Cursor::new(vec![]);
+    wav.write_to(&mut out).unwrap();
+    assert_eq!(out.get_ref().as_slice(), &file[..]);
+}
@@ -0,0 +1,267 @@
+use std::f64::consts::PI;
+
+/// Shape of the taper applied before a transform, trading main lobe width against how far
+/// the sidelobes fall.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowType {
+    /// first sidelobe at -43 dB but a slow falloff
+    Hamming,
+    /// -31 dB sidelobes falling off quickly, a good default
+    Hann,
+    /// -58 dB sidelobes
+    Blackman,
+    /// the four term window, -92 dB sidelobes and a wide main lobe
+    BlackmanHarris,
+    /// four terms like Blackman-Harris, -93 dB with faster falloff
+    Nuttall,
+    /// tunable through beta: 0 is rectangular, around 9 gives -90 dB sidelobes
+    Kaiser(f32),
+    /// the peak reads within a hundredth of a dB wherever a sine falls between bins, at the
+    /// cost of a very wide main lobe
+    FlatTop,
+    /// standard deviation as a fraction of half the window, usually below 0.5
+    Gaussian(f32),
+}
+
+/// Which end of the window the last sample lands on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    /// zero (or the lowest value) at both ends, for FIR filter design
+    Symmetric,
+    /// one period of a window a sample longer, so the last sample would start the next
+    /// period; what spectral analysis and overlap-add want
+    Periodic,
+}
+
+/// A window's samples along with the gains needed to read magnitudes through it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    window_type: WindowType,
+    symmetry: Symmetry,
+    samples: Box<[f32]>,
+}
+
+impl Window {
+    /// A single sample window is just 1, whatever the type.
+    pub fn new(window_type: WindowType, size: usize, symmetry: Symmetry) -> Self {
+        // samples are spread over one period of this length
+        let period = match symmetry {
+            Symmetry::Symmetric => size.saturating_sub(1),
+            Symmetry::Periodic => size,
+        };
+        let samples = (0..size)
+            .map(|i| match size {
+                1 => 1.0,
+                _ => window_type.evaluate(i as f64 / period as f64) as f32,
+            })
+            .collect();
+        Self {
+            window_type,
+            symmetry,
+            samples,
+        }
+    }
+
+    pub fn window_type(&self) -> WindowType {
+        self.window_type
+    }
+
+    pub fn symmetry(&self) -> Symmetry {
+        self.symmetry
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn size(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Mean of the window, which scales the magnitude of a sine centred on a bin.
+    pub fn coherent_gain(&self) -> f32 {
+        (self.sum() / self.size() as f64) as f32
+    }
+
+    /// Equivalent noise bandwidth in bins: how much wider than a bin the window spreads
+    /// broadband noise, so noise power reads that much higher relative to a sine.
+    pub fn noise_bandwidth(&self) -> f32 {
+        let power = self
+            .samples
+            .iter()
+            .map(|x| (*x as f64) * (*x as f64))
+            .sum::<f64>();
+        (self.size() as f64 * power / (self.sum() * self.sum())) as f32
+    }
+
+    /// Amplitude of the sine behind the unscaled bin `magnitude` of a signal windowed by
+    /// this, for bins between DC and Nyquist.
+    pub fn sine_amplitude(&self, magnitude: f32) -> f32 {
+        (2.0 * magnitude as f64 / self.sum()) as f32
+    }
+
+    fn sum(&self) -> f64 {
+        self.samples.iter().map(|x| *x as f64).sum()
+    }
+}
+
+impl WindowType {
+    // the window at `x`, from 0 at the first sample to 1 a period later
+    fn evaluate(self, x: f64) -> f64 {
+        match self {
+            WindowType::Hamming => cosine_sum(x, &[0.54, 0.46]),
+            WindowType::Hann => cosine_sum(x, &[0.5, 0.5]),
+            WindowType::Blackman => cosine_sum(x, &[0.42, 0.5, 0.08]),
+            WindowType::BlackmanHarris => cosine_sum(x, &[0.35875, 0.48829, 0.14128, 0.01168]),
+            WindowType::Nuttall => cosine_sum(x, &[0.355768, 0.487396, 0.144232, 0.012604]),
+            WindowType::Kaiser(beta) => kaiser(2.0 * x - 1.0, beta as f64),
+            WindowType::FlatTop => cosine_sum(
+                x,
+                &[
+                    0.21557895,
+                    0.41663158,
+                    0.277263158,
+                    0.083578947,
+                    0.006947368,
+                ],
+            ),
+            WindowType::Gaussian(sigma) => {
+                let distance = (2.0 * x - 1.0) / sigma as f64;
+                (-0.5 * distance * distance).exp()
+            }
+        }
+    }
+}
+
+// a0 - a1 cos(2πx) + a2 cos(4πx) - ..., the form of every window here bar two
+fn cosine_sum(x: f64, coefficients: &[f64]) -> f64 {
+    coefficients
+        .iter()
+        .enumerate()
+        .map(|(k, a)| {
+            let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+            sign * a * (2.0 * PI * k as f64 * x).cos()
+        })
+        .sum()
+}
+
+// Kaiser window over [-1, 1]
+pub(crate) fn kaiser(x: f64, beta: f64) -> f64 {
+    if x.abs() > 1.0 {
+        return 0.0;
+    }
+    bessel_i0(beta * (1.0 - x * x).sqrt()) / bessel_i0(beta)
+}
+
+// zeroth order modified Bessel function of the first kind, by its power series
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+    while term > sum * 1e-17 {
+        term *= (x / (2.0 * k)) * (x / (2.0 * k));
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+#[cfg(test)]
+const ALL_TYPES: [WindowType; 8] = [
+    WindowType::Hamming,
+    WindowType::Hann,
+    WindowType::Blackman,
+    WindowType::BlackmanHarris,
+    WindowType::Nuttall,
+    WindowType::Kaiser(8.0),
+    WindowType::FlatTop,
+    WindowType::Gaussian(0.4),
+];
+
+#[test]
+fn shapes() {
+    for window_type in ALL_TYPES {
+        let symmetric = Window::new(window_type, 65, Symmetry::Symmetric);
+        let samples = symmetric.samples();
+        assert!(samples
+            .iter()
+            .zip(samples.iter().rev())
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+        assert!((samples[32] - 1.0).abs() < 1e-6, "{:?}", window_type);
+
+        // periodic is the symmetric window one longer, missing its last sample
+        let periodic = Window::new(window_type, 64, Symmetry::Periodic);
+        assert!(periodic
+            .samples()
+            .iter()
+            .zip(samples)
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+
+        for symmetry in [Symmetry::Symmetric, Symmetry::Periodic] {
+            assert!(Window::new(window_type, 0, symmetry).samples().is_empty());
+            assert_eq!(Window::new(window_type, 1, symmetry).samples(), &[1.0]);
+        }
+    }
+    // Kaiser with no taper at all
+    let flat = Window::new(WindowType::Kaiser(0.0), 16, Symmetry::Periodic);
+    assert!(flat.samples().iter().all(|x| (x - 1.0).abs() < 1e-6));
+}
+
+#[test]
+fn gains() {
+    for (window_type, coherent_gain, noise_bandwidth) in [
+        (WindowType::Hamming, 0.54, 1.363),
+        (WindowType::Hann, 0.5, 1.5),
+        (WindowType::Blackman, 0.42, 1.727),
+        (WindowType::BlackmanHarris, 0.35875, 2.004),
+        (WindowType::Nuttall, 0.355768, 2.021),
+        (WindowType::FlatTop, 0.21557895, 3.770),
+    ] {
+        let window = Window::new(window_type, 4096, Symmetry::Periodic);
+        assert!((window.coherent_gain() - coherent_gain).abs() < 1e-5);
+        assert!(
+            (window.noise_bandwidth() - noise_bandwidth).abs() < 1e-3,
+            "{:?}: {}",
+            window_type,
+            window.noise_bandwidth()
+        );
+    }
+}
+
+#[test]
+fn corrects_magnitudes() {
+    use crate::fft::FFT;
+
+    // a sine of amplitude 0.3 on bin 85, then halfway to the next
+    for bin in [85.0, 85.5] {
+        let signal = (0..4096)
+            .map(|i| 0.3 * (2.0 * PI * bin * i as f64 / 4096.0).sin() as f32)
+            .collect::<Box<[f32]>>();
+        for window_type in ALL_TYPES {
+            let window = Window::new(window_type, signal.len(), Symmetry::Periodic);
+            let windowed = signal
+                .iter()
+                .zip(window.samples())
+                .map(|(a, b)| a * b)
+                .collect::<Box<[f32]>>();
+            let peak = FFT::real_fft(&windowed)
+                .unwrap()
+                .iter()
+                .map(|bin| bin.norm())
+                .fold(0.0, f32::max);
+            let amplitude = window.sine_amplitude(peak);
+            // only the flat-top keeps its reading between bins
+            let tolerance = match (window_type, bin == 85.0) {
+                (_, true) | (WindowType::FlatTop, _) => 1e-3,
+                _ => 0.06,
+            };
+            assert!(
+                (amplitude - 0.3).abs() < tolerance,
+                "{:?} at bin {}: {}",
+                window_type,
+                bin,
+                amplitude
+            );
+        }
+    }
+}
@@ -0,0 +1,577 @@
+use std::mem;
+
+use num_complex::Complex;
+
+use crate::{audio_analysis::find_max_float, fft::FftPlan};
+
+// threshold on the normalized difference a dip has to reach, as suggested by the YIN paper
+const DEFAULT_THRESHOLD: f32 = 0.1;
+// pYIN tries thresholds from 0.01 to 1 in steps of 0.01, weighted by a beta distribution
+// with a mean of 0.15
+const THRESHOLDS: usize = 100;
+const THRESHOLD_BETA: (f64, f64) = (2.0, 34.0 / 3.0);
+// share of a threshold's weight the lowest dip gets when nothing reaches the threshold
+const LOWEST_DIP_WEIGHT: f32 = 0.01;
+// spacing of the pYIN pitch grid in cents, and how many steps of it the pitch can move
+// between two frames
+const PITCH_STEP: f32 = 10.0;
+const MAX_JUMP: usize = 25;
+// chance of a frame switching between voiced and unvoiced
+const VOICING_SWITCH: f32 = 0.01;
+
+/// A pitch found in the time domain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchEstimate {
+    pub frequency: f32,
+    /// from 0 to 1: how periodic the frame is at that pitch for YIN, and the chance the frame
+    /// is voiced at all for pYIN
+    pub confidence: f32,
+}
+
+/// The YIN pitch detector, from de Cheveigné and Kawahara's 2002 paper.
+///
+/// Compares the frame with itself delayed by every lag a pitch between the lowest and
+/// highest frequency could have, divides the differences by their running mean, and takes
+/// the first lag whose dip reaches the threshold, placed between samples with a parabola.
+/// Unlike the spectrum's loudest bin it doesn't jump octaves on strong harmonics, and only
+/// needs frames of two of the longest period.
+pub struct Yin {
+    sample_rate: u32,
+    frame_len: usize,
+    min_lag: usize,
+    max_lag: usize,
+    threshold: f32,
+    plan: FftPlan<f64>,
+    buffer: Box<[f64]>,
+    spectrum: Box<[Complex<f64>]>,
+    window_spectrum: Box<[Complex<f64>]>,
+    scratch: Box<[Complex<f64>]>,
+    // the normalized difference at every lag up to one past max_lag, so the parabola
+    // always has a neighbour on each side
+    difference: Box<[f32]>,
+}
+
+impl Yin {
+    /// # Panics
+    /// If the frequencies aren't a positive range, or `frame_len` can't hold two periods
+    /// of `min_frequency`.
+    pub fn new(sample_rate: u32, frame_len: usize, min_frequency: f32, max_frequency: f32) -> Self {
+        assert!(
+            0.0 < min_frequency && min_frequency < max_frequency,
+            "YIN needs a range of frequencies"
+        );
+        let max_lag = Self::max_lag(sample_rate, min_frequency);
+        let min_lag = ((sample_rate as f32 / max_frequency) as usize).clamp(2, max_lag);
+        assert!(
+            frame_len >= Self::min_frame_len(sample_rate, min_frequency),
+            "YIN frames have to hold two of the longest period"
+        );
+        let plan = FftPlan::new(frame_len.next_power_of_two()).unwrap();
+        Self {
+            sample_rate,
+            frame_len,
+            min_lag,
+            max_lag,
+            threshold: DEFAULT_THRESHOLD,
+            buffer: vec![0.0; plan.size()].into_boxed_slice(),
+            spectrum: vec![Complex::new(0.0, 0.0); plan.size() / 2 + 1].into_boxed_slice(),
+            window_spectrum: vec![Complex::new(0.0, 0.0); plan.size() / 2 + 1].into_boxed_slice(),
+            scratch: vec![Complex::new(0.0, 0.0); plan.scratch_len()].into_boxed_slice(),
+            difference: vec![0.0; max_lag + 2].into_boxed_slice(),
+            plan,
+        }
+    }
+
+    /// The shortest frame `new` accepts for `min_frequency`: two of its periods, plus a
+    /// sample each for the parabola.
+    pub fn min_frame_len(sample_rate: u32, min_frequency: f32) -> usize {
+        2 * (Self::max_lag(sample_rate, min_frequency) + 1)
+    }
+
+    fn max_lag(sample_rate: u32, min_frequency: f32) -> usize {
+        (sample_rate as f32 / min_frequency).ceil() as usize
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// Lower thresholds pass over frames that are less clearly periodic instead of
+    /// guessing.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    /// The pitch of `samples`, or `None` if no lag is periodic enough to reach the threshold.
+    ///
+    /// # Panics
+    /// If `samples` isn't `frame_len()` long.
+    pub fn detect(&mut self, samples: &[f32]) -> Option<PitchEstimate> {
+        self.normalize_difference(samples);
+        let lag = self.dip(self.threshold)?;
+        Some(PitchEstimate {
+            frequency: self.refine(lag),
+            confidence: (1.0 - self.difference[lag]).clamp(0.0, 1.0),
+        })
+    }
+
+    // the cumulative mean normalized difference of `samples` at every lag, which starts at
+    // 1 and only drops near 0 at multiples of the period
+    fn normalize_difference(&mut self, samples: &[f32]) {
+        assert_eq!(samples.len(), self.frame_len, "YIN used on the wrong size");
+        let lags = self.difference.len();
+        // the stretch compared with its delayed copies, short enough that none run off the end
+        let width = self.frame_len - (lags - 1);
+
+        // the cross term sum of x[j]·x[j + lag], as a correlation through the FFT; the
+        // delayed copies never pass the end of the frame, so nothing wraps around
+        self.buffer.fill(0.0);
+        self.buffer
+            .iter_mut()
+            .zip(samples)
+            .for_each(|(value, sample)| *value = *sample as f64);
+        self.plan
+            .real_forward_with_scratch(&self.buffer, &mut self.spectrum, &mut self.scratch);
+        self.buffer[width..].fill(0.0);
+        self.plan.real_forward_with_scratch(
+            &self.buffer,
+            &mut self.window_spectrum,
+            &mut self.scratch,
+        );
+        self.spectrum
+            .iter_mut()
+            .zip(self.window_spectrum.iter())
+            .for_each(|(bin, window)| *bin *= window.conj());
+        self.plan.real_inverse_with_scratch(
+            &mut self.spectrum,
+            &mut self.buffer,
+            &mut self.scratch,
+            true,
+        );
+
+        let square = |index: usize| (samples[index] as f64).powi(2);
+        let first = (0..width).map(square).sum::<f64>();
+        let mut delayed = first;
+        let mut sum = 0.0;
+        for lag in 0..lags {
+            let difference = (first + delayed - 2.0 * self.buffer[lag]).max(0.0);
+            sum += difference;
+            self.difference[lag] = match lag == 0 || sum <= 0.0 {
+                true => 1.0,
+                false => (difference * lag as f64 / sum) as f32,
+            };
+            if lag + 1 < lags {
+                delayed += square(lag + width) - square(lag);
+            }
+        }
+    }
+
+    // the first lag in range below `threshold`, followed down to the bottom of its dip
+    fn dip(&self, threshold: f32) -> Option<usize> {
+        let mut lag =
+            (self.min_lag..=self.max_lag).find(|lag| self.difference[*lag] < threshold)?;
+        while lag < self.max_lag && self.difference[lag + 1] < self.difference[lag] {
+            lag += 1;
+        }
+        Some(lag)
+    }
+
+    fn lowest_dip(&self) -> usize {
+        (self.min_lag..=self.max_lag)
+            .min_by(|a, b| self.difference[*a].total_cmp(&self.difference[*b]))
+            .unwrap()
+    }
+
+    // frequency of the bottom of a parabola through `lag` and its neighbours
+    fn refine(&self, lag: usize) -> f32 {
+        let (left, center, right) = (
+            self.difference[lag - 1],
+            self.difference[lag],
+            self.difference[lag + 1],
+        );
+        let offset = match center <= left && center <= right && left + right > 2.0 * center {
+            true => 0.5 * (left - right) / (left - 2.0 * center + right),
+            false => 0.0,
+        };
+        self.sample_rate as f32 / (lag as f32 + offset)
+    }
+}
+
+// one frame of pYIN's observations
+#[derive(Default)]
+struct Observation {
+    // log chance of the frame given each state, voiced states first
+    emissions: Vec<f32>,
+    // the likeliest candidate frequency in each voiced bin that has any
+    frequencies: Vec<(usize, f32, f32)>,
+    voiced: f32,
+}
+
+/// Probabilistic YIN, from Mauch and Dixon's 2014 paper.
+///
+/// Rather than one threshold, tries a hundred of them, so each frame gives a few candidate
+/// pitches with a probability each. A hidden Markov model over a 10 cent pitch grid, with
+/// voiced and unvoiced states, then picks the likeliest path through them, which keeps the
+/// pitch from jumping between candidates and decides when the signal is voiced at all.
+///
+/// `process` follows the path as frames arrive, for live input; `track` decodes a whole
+/// signal at once, which can also revise earlier frames in the light of later ones.
+pub struct Pyin {
+    yin: Yin,
+    min_frequency: f32,
+    bins: usize,
+    threshold_weights: Box<[f32]>,
+    // log chance of each jump from -MAX_JUMP to MAX_JUMP bins
+    jumps: Box<[f32]>,
+    // log chance of the likeliest path into each state, empty before the first frame
+    path: Box<[f32]>,
+    // work buffers `process` reuses from one frame to the next
+    candidates: Vec<(usize, f32)>,
+    observation: Observation,
+    next_path: Box<[f32]>,
+}
+
+impl Pyin {
+    /// # Panics
+    /// Like `Yin::new`.
+    pub fn new(sample_rate: u32, frame_len: usize, min_frequency: f32, max_frequency: f32) -> Self {
+        let yin = Yin::new(sample_rate, frame_len, min_frequency, max_frequency);
+        let bins = (1200.0 * (max_frequency / min_frequency).log2() / PITCH_STEP) as usize + 1;
+
+        let (alpha, beta) = THRESHOLD_BETA;
+        let weights = (1..=THRESHOLDS)
+            .map(|i| {
+                let x = i as f64 / THRESHOLDS as f64;
+                x.powf(alpha - 1.0) * (1.0 - x).powf(beta - 1.0)
+            })
+            .collect::<Box<[f64]>>();
+        let total = weights.iter().sum::<f64>();
+        let threshold_weights = weights
+            .iter()
+            .map(|weight| (weight / total) as f32)
+            .collect();
+
+        // triangular, most likely to stay put
+        let jumps = (0..=2 * MAX_JUMP)
+            .map(|offset| (MAX_JUMP + 1 - offset.abs_diff(MAX_JUMP)) as f32)
+            .collect::<Box<[f32]>>();
+        let total = jumps.iter().sum::<f32>();
+        let jumps = jumps.iter().map(|jump| (jump / total).ln()).collect();
+
+        Self {
+            yin,
+            min_frequency,
+            bins,
+            threshold_weights,
+            jumps,
+            path: Box::default(),
+            candidates: vec![],
+            observation: Observation::default(),
+            next_path: Box::default(),
+        }
+    }
+
+    pub fn frame_len(&self) -> usize {
+        self.yin.frame_len()
+    }
+
+    /// Forgets the path so far, ready for an unrelated signal.
+    pub fn reset(&mut self) {
+        self.path = Box::default();
+    }
+
+    /// The pitch at the end of the likeliest path through every frame since the last reset,
+    /// or `None` if that path ends unvoiced.
+    ///
+    /// # Panics
+    /// If `samples` isn't `frame_len()` long.
+    pub fn process(&mut self, samples: &[f32]) -> Option<PitchEstimate> {
+        let mut observation = mem::take(&mut self.observation);
+        self.observe(samples, &mut observation);
+        if self.path.is_empty() {
+            self.path = observation.emissions.as_slice().into();
+        } else {
+            let mut next = mem::take(&mut self.next_path);
+            if next.len() != self.path.len() {
+                next = vec![0.0; self.path.len()].into_boxed_slice();
+            }
+            self.step(&self.path, &observation.emissions, &mut next, None);
+            self.next_path = mem::replace(&mut self.path, next);
+        }
+        let (state, _) = find_max_float(&self.path);
+        let estimate = self.estimate(state, &observation);
+        self.observation = observation;
+        estimate
+    }
+
+    /// The pitch of each frame of `samples`, frame `k` starting at sample `k * hop`, along
+    /// the single likeliest path through all of them. Leaves the path `process` follows
+    /// alone.
+    ///
+    /// # Panics
+    /// If `hop` is zero.
+    pub fn track(&mut self, samples: &[f32], hop: usize) -> Box<[Option<PitchEstimate>]> {
+        assert!(hop > 0, "pYIN frames need a hop of at least one sample");
+        let mut observations = vec![];
+        // for each frame after the first, the state the likeliest path into each came from
+        let mut origins = vec![];
+        let mut path = Box::<[f32]>::default();
+        for start in (0..).map(|index| index * hop) {
+            let Some(frame) = samples.get(start..start + self.frame_len()) else {
+                break;
+            };
+            let mut observation = Observation::default();
+            self.observe(frame, &mut observation);
+            path = match path.is_empty() {
+                true => observation.emissions.as_slice().into(),
+                false => {
+                    let mut origin = vec![0; path.len()];
+                    let mut next = vec![0.0; path.len()].into_boxed_slice();
+                    self.step(&path, &observation.emissions, &mut next, Some(&mut origin));
+                    origins.push(origin);
+                    next
+                }
+            };
+            observations.push(observation);
+        }
+        if observations.is_empty() {
+            return Box::default();
+        }
+
+        let (mut state, _) = find_max_float(&path);
+        let mut estimates = vec![None; observations.len()];
+        for (index, observation) in observations.iter().enumerate().rev() {
+            estimates[index] = self.estimate(state, observation);
+            if index > 0 {
+                state = origins[index - 1][state];
+            }
+        }
+        estimates.into_boxed_slice()
+    }
+
+    // fills `observation` with the observations of one frame, reusing its buffers
+    fn observe(&mut self, samples: &[f32], observation: &mut Observation) {
+        self.yin.normalize_difference(samples);
+        // lags any threshold picks, with the combined weight of those thresholds
+        let mut candidates = mem::take(&mut self.candidates);
+        candidates.clear();
+        for (index, weight) in self.threshold_weights.iter().enumerate() {
+            let threshold = (index + 1) as f32 / THRESHOLDS as f32;
+            let (lag, weight) = match self.yin.dip(threshold) {
+                Some(lag) => (lag, *weight),
+                None => (self.yin.lowest_dip(), weight * LOWEST_DIP_WEIGHT),
+            };
+            match candidates
+                .iter_mut()
+                .find(|(candidate, _)| *candidate == lag)
+            {
+                Some((_, total)) => *total += weight,
+                None => candidates.push((lag, weight)),
+            }
+        }
+
+        // chances first, turned into log chances in place at the end
+        let probabilities = &mut observation.emissions;
+        probabilities.clear();
+        probabilities.resize(2 * self.bins, 0.0);
+        let frequencies = &mut observation.frequencies;
+        frequencies.clear();
+        for (lag, probability) in candidates.drain(..) {
+            let frequency = self.yin.refine(lag);
+            let Some(bin) = self.bin(frequency) else {
+                continue;
+            };
+            probabilities[bin] += probability;
+            match frequencies.iter_mut().find(|(other, _, _)| *other == bin) {
+                Some(best) if best.2 < probability => *best = (bin, frequency, probability),
+                Some(_) => {}
+                None => frequencies.push((bin, frequency, probability)),
+            }
+        }
+        self.candidates = candidates;
+        let voiced = probabilities.iter().sum::<f32>().min(1.0);
+        probabilities[self.bins..].fill((1.0 - voiced) / self.bins as f32);
+        probabilities
+            .iter_mut()
+            .for_each(|probability| *probability = probability.max(f32::MIN_POSITIVE).ln());
+        observation.voiced = voiced;
+    }
+
+    // one Viterbi step: the log chance of the likeliest path into each state, written to
+    // `next`, optionally noting which state it came from
+    fn step(
+        &self,
+        path: &[f32],
+        emissions: &[f32],
+        next: &mut [f32],
+        mut origins: Option<&mut [usize]>,
+    ) {
+        let (stay, switch) = ((1.0 - VOICING_SWITCH).ln(), VOICING_SWITCH.ln());
+        for (state, value) in next.iter_mut().enumerate() {
+            let (bin, voiced) = (state % self.bins, state < self.bins);
+            let mut best = (f32::NEG_INFINITY, state);
+            for (offset, jump) in self.jumps.iter().enumerate() {
+                let Some(from_bin) = (bin + offset)
+                    .checked_sub(MAX_JUMP)
+                    .filter(|from_bin| *from_bin < self.bins)
+                else {
+                    continue;
+                };
+                for (from, from_voiced) in [(from_bin, true), (from_bin + self.bins, false)] {
+                    let voicing = if from_voiced == voiced { stay } else { switch };
+                    let score = path[from] + jump + voicing;
+                    if score > best.0 {
+                        best = (score, from);
+                    }
+                }
+            }
+            *value = best.0 + emissions[state];
+            if let Some(origins) = origins.as_deref_mut() {
+                origins[state] = best.1;
+            }
+        }
+        // only the differences matter, so keep the numbers from running away
+        let (_, top) = find_max_float(next);
+        let top = *top;
+        next.iter_mut().for_each(|value| *value -= top);
+    }
+
+    fn estimate(&self, state: usize, observation: &Observation) -> Option<PitchEstimate> {
+        if state >= self.bins {
+            return None;
+        }
+        // the exact candidate if there is one, otherwise the middle of the bin
+        let frequency = observation
+            .frequencies
+            .iter()
+            .find(|(bin, _, _)| *bin == state)
+            .map_or_else(|| self.bin_frequency(state), |(_, frequency, _)| *frequency);
+        Some(PitchEstimate {
+            frequency,
+            confidence: observation.voiced,
+        })
+    }
+
+    fn bin(&self, frequency: f32) -> Option<usize> {
+        let bin = (1200.0 * (frequency / self.min_frequency).log2() / PITCH_STEP).round();
+        (bin >= 0.0 && (bin as usize) < self.bins).then_some(bin as usize)
+    }
+
+    fn bin_frequency(&self, bin: usize) -> f32 {
+        self.min_frequency * 2.0f32.powf(bin as f32 * PITCH_STEP / 1200.0)
+    }
+}
+
+/// pYIN pitch of every frame of a whole signal; see `Pyin::track`.
+pub fn pyin(
+    samples: &[f32],
+    sample_rate: u32,
+    frame_len: usize,
+    hop: usize,
+    min_frequency: f32,
+    max_frequency: f32,
+) -> Box<[Option<PitchEstimate>]> {
+    Pyin::new(sample_rate, frame_len, min_frequency, max_frequency).track(samples, hop)
+}
+
+// a plucked string's worth of harmonics over `frequency`, the fundamental the quietest
+#[cfg(test)]
+pub(crate) fn harmonic_tone(frequency: f64, len: usize) -> Box<[f32]> {
+    (0..len)
+        .map(|i| {
+            let t = i as f64 / 48000.0;
+            [0.2, 1.0, 0.8, 0.6, 0.3]
+                .iter()
+                .enumerate()
+                .map(|(k, amplitude)| {
+                    let harmonic = (k + 1) as f64;
+                    amplitude
+                        * (2.0 * std::f64::consts::PI * harmonic * frequency * t + k as f64).sin()
+                })
+                .sum::<f64>() as f32
+                * 0.3
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn cents(found: f32, expected: f64) -> f64 {
+    1200.0 * (found as f64 / expected).log2()
+}
+
+#[test]
+fn yin() {
+    // low E on a guitar, and a few notes up from there, on frames of under 43 ms
+    for frequency in [82.41, 110.0, 146.83, 329.63, 440.3, 1046.5] {
+        let samples = harmonic_tone(frequency, 2048);
+        let mut yin = Yin::new(48000, 2048, 60.0, 2000.0);
+        let estimate = yin.detect(&samples).unwrap();
+        assert!(
+            cents(estimate.frequency, frequency).abs() < 2.0,
+            "{} Hz: {:?}",
+            frequency,
+            estimate
+        );
+        assert!(estimate.confidence > 0.9);
+    }
+
+    // the shortest frame is accepted
+    assert_eq!(Yin::min_frame_len(48000, 60.0), 1602);
+    let yin = Yin::new(48000, 1602, 60.0, 2000.0);
+    assert_eq!(yin.frame_len(), 1602);
+
+    // nothing periodic in silence or noise
+    let mut yin = Yin::new(48000, 4096, 60.0, 2000.0);
+    assert_eq!(yin.detect(&[0.0; 4096]), None);
+    assert_eq!(yin.detect(&crate::fft::random_signal::<f32>(4096, 5)), None);
+}
+
+#[test]
+fn pyin_tracks_notes() {
+    // a second of low E, a quarter of silence and a second of A, with a little noise
+    let noise = crate::fft::random_signal::<f32>(108000, 6);
+    let signal = harmonic_tone(82.41, 48000)
+        .iter()
+        .chain(&[0.0; 12000])
+        .chain(harmonic_tone(110.0, 48000).iter())
+        .zip(noise.iter())
+        .map(|(sample, noise)| sample + 0.01 * noise)
+        .collect::<Box<[f32]>>();
+
+    let estimates = pyin(&signal, 48000, 2048, 512, 60.0, 2000.0);
+    assert_eq!(estimates.len(), (signal.len() - 2048) / 512 + 1);
+    let mut pyin = Pyin::new(48000, 2048, 60.0, 2000.0);
+    for (index, estimate) in estimates.iter().enumerate() {
+        let (start, end) = (index * 512, index * 512 + 2048);
+        let expected = match (start, end) {
+            (_, end) if end <= 48000 => Some(82.41),
+            (start, end) if start >= 48000 && end <= 60000 => None,
+            (start, _) if start >= 60000 => Some(110.0),
+            // frames straddling a change
+            _ => continue,
+        };
+        match (estimate, expected) {
+            (Some(estimate), Some(expected)) => assert!(
+                cents(estimate.frequency, expected).abs() < 2.0,
+                "frame {}: {:?}",
+                index,
+                estimate
+            ),
+            (None, None) => {}
+            _ => panic!("frame {}: {:?} instead of {:?}", index, estimate, expected),
+        }
+
+        // following along frame by frame agrees once it has settled on a note
+        let live = pyin.process(&signal[start..end]);
+        assert_eq!(live.is_some(), expected.is_some(), "frame {}", index);
+    }
+    assert!(pyin.track(&signal[..1000], 512).is_empty());
+}
@@ -0,0 +1,263 @@
+use std::f64::consts::PI;
+
+use num_complex::Complex;
+
+use crate::{dft::TransformType, fft::FftPlan};
+
+/// A single DFT bin at any frequency, not just multiples of sample_rate / N, for checking
+/// the level of one known tone far more cheaply than a full transform.
+#[derive(Debug, Clone, Copy)]
+pub struct Goertzel {
+    frequency: f32,
+    // radians per sample
+    omega: f64,
+}
+
+impl Goertzel {
+    pub fn new(sample_rate: u32, frequency: f32) -> Self {
+        Self {
+            frequency,
+            omega: 2.0 * PI * frequency as f64 / sample_rate as f64,
+        }
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    // the second order recurrence, in f64 since it loses precision near DC; returns its
+    // last two states
+    fn run(&self, samples: &[f32]) -> (f64, f64) {
+        let coefficient = 2.0 * self.omega.cos();
+        let (mut s1, mut s2) = (0.0, 0.0);
+        for sample in samples {
+            (s1, s2) = (*sample as f64 + coefficient * s1 - s2, s1);
+        }
+        (s1, s2)
+    }
+
+    /// The DFT of `samples` at this frequency, sum of x[n]·e^(-iωn), phase included.
+    pub fn evaluate(&self, samples: &[f32]) -> Complex<f32> {
+        let (s1, s2) = self.run(samples);
+        // the recurrence ends up rotated to the last sample, so turn it back to the first
+        let last = Complex::new(s1, 0.0) - Complex::from_polar(s2, -self.omega);
+        let value =
+            last * Complex::from_polar(1.0, -self.omega * samples.len().saturating_sub(1) as f64);
+        Complex::new(value.re as f32, value.im as f32)
+    }
+
+    /// Squared magnitude of `evaluate`, which doesn't need the phase correction.
+    pub fn power(&self, samples: &[f32]) -> f32 {
+        let (s1, s2) = self.run(samples);
+        (s1 * s1 + s2 * s2 - 2.0 * self.omega.cos() * s1 * s2) as f32
+    }
+}
+
+/// Chirp-z transform over a narrow band: evenly spaced DFT bins from `low` to `high` Hz,
+/// as finely spaced as asked for, over a fixed number of samples.
+///
+/// Built on Bluestein's algorithm, so each call costs two FFTs of a power of two at least
+/// `input_len + bins - 1` long whatever the spacing. Runs in f64, as the chirps reach
+/// phases in the thousands of radians over long windows.
+pub struct ZoomFft {
+    low: f64,
+    step: f64,
+    input_len: usize,
+    bins: usize,
+    plan: FftPlan<f64>,
+    // e^(-iω₀n - iΔωn²/2) for each input sample
+    pre: Box<[Complex<f64>]>,
+    // transform of the conjugate chirp wrapped around the plan size, divided by it
+    kernel: Box<[Complex<f64>]>,
+    // e^(-iΔωk²/2) for each bin
+    post: Box<[Complex<f64>]>,
+    buffer: Box<[Complex<f64>]>,
+    scratch: Box<[Complex<f64>]>,
+}
+
+impl ZoomFft {
+    /// # Panics
+    /// If `input_len` or `bins` is zero.
+    pub fn new(sample_rate: u32, input_len: usize, low: f32, high: f32, bins: usize) -> Self {
+        assert!(input_len > 0 && bins > 0, "zoom FFTs need samples and bins");
+        let (low, high) = (low as f64, high as f64);
+        let step = match bins {
+            1 => 0.0,
+            bins => (high - low) / (bins - 1) as f64,
+        };
+        let start = 2.0 * PI * low / sample_rate as f64;
+        let delta = 2.0 * PI * step / sample_rate as f64;
+
+        let size = (input_len + bins - 1).next_power_of_two();
+        let plan = FftPlan::new(size).unwrap();
+        let chirp = |n: usize| Complex::from_polar(1.0, -delta * (n * n) as f64 / 2.0);
+        let pre = (0..input_len)
+            .map(|n| chirp(n) * Complex::from_polar(1.0, -start * n as f64))
+            .collect();
+        let post = (0..bins).map(chirp).collect();
+        let mut kernel = vec![Complex::new(0.0, 0.0); size];
+        for (m, value) in kernel[..bins].iter_mut().enumerate() {
+            *value = chirp(m).conj();
+        }
+        for m in 1..input_len {
+            kernel[size - m] = chirp(m).conj();
+        }
+        plan.process(&mut kernel, TransformType::Forward, true);
+
+        Self {
+            low,
+            step,
+            input_len,
+            bins,
+            pre,
+            kernel: kernel.into_boxed_slice(),
+            post,
+            buffer: vec![Complex::new(0.0, 0.0); size].into_boxed_slice(),
+            scratch: vec![Complex::new(0.0, 0.0); plan.scratch_len()].into_boxed_slice(),
+            plan,
+        }
+    }
+
+    pub fn input_len(&self) -> usize {
+        self.input_len
+    }
+
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+
+    /// Frequency of `bin` in Hz, which may be fractional.
+    pub fn frequency(&self, bin: f32) -> f32 {
+        (self.low + self.step * bin as f64) as f32
+    }
+
+    /// The DFT of `samples` at each bin, as `Goertzel::evaluate` would give it.
+    ///
+    /// # Panics
+    /// If `samples` isn't `input_len()` long.
+    pub fn process(&mut self, samples: &[f32]) -> Box<[Complex<f32>]> {
+        let mut output = vec![Complex::new(0.0, 0.0); self.bins].into_boxed_slice();
+        self.process_into(samples, &mut output);
+        output
+    }
+
+    /// Like `process`, writing the bins to `output` instead of allocating.
+    ///
+    /// # Panics
+    /// If `samples` isn't `input_len()` long or `output` isn't `bins()` long.
+    pub fn process_into(&mut self, samples: &[f32], output: &mut [Complex<f32>]) {
+        assert_eq!(
+            samples.len(),
+            self.input_len,
+            "zoom FFT used on the wrong size"
+        );
+        assert_eq!(output.len(), self.bins, "zoom FFT output of the wrong size");
+        for (n, value) in self.buffer.iter_mut().enumerate() {
+            *value = match samples.get(n) {
+                Some(sample) => self.pre[n] * *sample as f64,
+                None => Complex::new(0.0, 0.0),
+            };
+        }
+        self.plan.process_with_scratch(
+            &mut self.buffer,
+            &mut self.scratch,
+            TransformType::Forward,
+            false,
+        );
+        self.buffer
+            .iter_mut()
+            .zip(self.kernel.iter())
+            .for_each(|(value, kernel)| *value *= kernel);
+        self.plan.process_with_scratch(
+            &mut self.buffer,
+            &mut self.scratch,
+            TransformType::Inverse,
+            false,
+        );
+        output
+            .iter_mut()
+            .zip(self.post.iter().zip(self.buffer.iter()))
+            .for_each(|(out, (post, value))| {
+                let value = post * value;
+                *out = Complex::new(value.re as f32, value.im as f32);
+            });
+    }
+}
+
+#[cfg(test)]
+fn direct_dft(samples: &[f32], frequency: f64, sample_rate: u32) -> Complex<f64> {
+    samples
+        .iter()
+        .enumerate()
+        .map(|(n, sample)| {
+            *sample as f64
+                * Complex::from_polar(1.0, -2.0 * PI * frequency * n as f64 / sample_rate as f64)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+fn test_signal(len: usize) -> Box<[f32]> {
+    (0..len)
+        .map(|i| {
+            let t = i as f64 / 48000.0;
+            ((2.0 * PI * 440.3 * t).sin() + 0.5 * (2.0 * PI * 1234.5 * t + 1.0).cos()) as f32
+        })
+        .collect()
+}
+
+#[test]
+fn goertzel() {
+    use crate::fft::FFT;
+
+    let samples = test_signal(4800);
+    // on a bin it's that bin of the FFT
+    let spectrum = FFT::real_fft(&samples).unwrap();
+    let goertzel = Goertzel::new(48000, 440.0);
+    let value = goertzel.evaluate(&samples);
+    assert!((value - spectrum[44]).norm() < 1e-3 * spectrum[44].norm());
+    assert!((goertzel.power(&samples) - value.norm_sqr()).abs() < 1e-3 * value.norm_sqr());
+
+    // and anywhere in between it's the direct sum
+    for frequency in [0.0, 17.3, 440.3, 1234.5, 23999.9] {
+        let expected = direct_dft(&samples, frequency as f64, 48000);
+        let value = Goertzel::new(48000, frequency).evaluate(&samples);
+        let value = Complex::new(value.re as f64, value.im as f64);
+        assert!(
+            (value - expected).norm() < 1e-3 * expected.norm().max(1.0),
+            "{}: {} vs {}",
+            frequency,
+            value,
+            expected
+        );
+    }
+    assert_eq!(
+        Goertzel::new(48000, 440.0).evaluate(&[]),
+        Complex::new(0.0, 0.0)
+    );
+}
+
+#[test]
+fn zoom_matches_goertzel() {
+    let samples = test_signal(10000);
+    for (low, high, bins) in [(430.0, 450.0, 201), (1200.0, 1300.0, 7), (440.0, 440.0, 1)] {
+        let mut zoom = ZoomFft::new(48000, samples.len(), low, high, bins);
+        let values = zoom.process(&samples);
+        assert_eq!(values.len(), bins);
+        let mut reused = vec![Complex::new(1.0, 1.0); bins];
+        zoom.process_into(&samples, &mut reused);
+        assert_eq!(&reused[..], &values[..]);
+        for (bin, value) in values.iter().enumerate() {
+            let frequency = zoom.frequency(bin as f32);
+            let expected = direct_dft(&samples, frequency as f64, 48000);
+            let value = Complex::new(value.re as f64, value.im as f64);
+            assert!(
+                (value - expected).norm() < 1e-3 * expected.norm().max(1.0),
+                "{}: {} vs {}",
+                frequency,
+                value,
+                expected
+            );
+        }
+    }
+}